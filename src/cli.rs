@@ -0,0 +1,216 @@
+use std::io::BufRead;
+use std::net::IpAddr;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::json;
+
+use crate::config::{DEFAULT_DB_PATH, DEFAULT_SETTINGS_PATH, DEFAULT_STATIC_DIR, SERVER_PORT};
+use crate::db::accounts::{AccountStore, Role, User};
+use crate::db::audit::AuditStore;
+use crate::db::stats::{AccountStats, StatsStore};
+use crate::db::Database;
+use crate::names::name_key;
+
+/// Agar.io clone game server. Runs `serve` when no command is given.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// SQLite database file
+    #[arg(long, global = true, default_value = DEFAULT_DB_PATH)]
+    pub db: String,
+
+    /// Runtime settings file (JSON); a missing file means defaults
+    #[arg(long, global = true, default_value = DEFAULT_SETTINGS_PATH)]
+    pub config: String,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options for running `serve` implicitly
+    #[command(flatten)]
+    pub serve: ServeArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the game server
+    Serve(ServeArgs),
+    /// Create or upgrade the database schema, then exit
+    Migrate,
+    /// Give an account the admin role, creating the account if needed
+    CreateAdmin {
+        username: String,
+        /// Read the password from the first line of stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Set a new password for an account and sign out all its sessions
+    ResetPassword {
+        username: String,
+        /// Read the password from the first line of stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Write every account's lifetime stats, best score first
+    ExportStats {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = StatsFormat::Csv)]
+        format: StatsFormat,
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Compact the database file
+    Vacuum,
+    /// Copy the database to a new file; safe while the server is running
+    Backup { path: String },
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "0.0.0.0")]
+    pub bind: IpAddr,
+
+    /// HTTP/WebSocket port
+    #[arg(long, short, default_value_t = SERVER_PORT)]
+    pub port: u16,
+
+    /// Directory holding the web client
+    #[arg(long, default_value = DEFAULT_STATIC_DIR)]
+    pub static_dir: String,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StatsFormat {
+    Csv,
+    Json,
+}
+
+/// Opens the SQLite database at `path`, creating its directory and
+/// bringing the schema up to date.
+pub fn open_database(path: &str) -> Database {
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(dir).ok();
+    }
+    Database::new(path)
+}
+
+/// Runs a maintenance command against the database at `db_path`.
+pub fn run(command: Command, db_path: &str) -> Result<(), String> {
+    let db = open_database(db_path);
+    match command {
+        Command::Serve(_) => unreachable!("serve is handled by main"),
+        Command::Migrate => println!("✅ Database {} is up to date", db_path),
+        Command::CreateAdmin {
+            username,
+            password_stdin,
+        } => create_admin(&db, &username, password_stdin)?,
+        Command::ResetPassword {
+            username,
+            password_stdin,
+        } => {
+            let user = find_user(&db, &username)?;
+            let password = read_password(password_stdin)?;
+            db.reset_password(user.id, &password)?;
+            audit(&db, "reset_password", &user, json!(null));
+            println!("✅ Password for {} reset; all sessions signed out", user.username);
+        }
+        Command::ExportStats { format, output } => {
+            let stats = db.all_stats()?;
+            let text = match format {
+                StatsFormat::Csv => stats_csv(&stats),
+                StatsFormat::Json => serde_json::to_string_pretty(&stats)
+                    .map_err(|e| format!("Failed to encode stats: {}", e))?,
+            };
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, text + "\n").map_err(|e| format!("{}: {}", path, e))?;
+                    eprintln!("✅ Wrote stats for {} accounts to {}", stats.len(), path);
+                }
+                None => println!("{}", text),
+            }
+        }
+        Command::Vacuum => {
+            db.vacuum()?;
+            println!("✅ Vacuumed {}", db_path);
+        }
+        Command::Backup { path } => {
+            db.backup(&path)?;
+            println!("✅ Backed up {} to {}", db_path, path);
+        }
+    }
+    Ok(())
+}
+
+fn create_admin(db: &Database, username: &str, password_stdin: bool) -> Result<(), String> {
+    if let Ok(user) = find_user(db, username) {
+        db.set_role(user.id, Role::Admin)?;
+        audit(db, "set_role", &user, json!({ "role": Role::Admin }));
+        println!("✅ {} (id {}) is now an admin", user.username, user.id);
+        return Ok(());
+    }
+    let password = read_password(password_stdin)?;
+    let user = db.register(username, &password)?;
+    db.set_role(user.id, Role::Admin)?;
+    audit(db, "create_admin", &user, json!(null));
+    println!("✅ Created admin account {} (id {})", user.username, user.id);
+    Ok(())
+}
+
+fn find_user(db: &Database, username: &str) -> Result<User, String> {
+    db.find_user_by_name_key(&name_key(username))
+        .ok_or_else(|| format!("No account named {}", username))
+}
+
+fn read_password(from_stdin: bool) -> Result<String, String> {
+    if from_stdin {
+        let mut line = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read password: {}", e))?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    let prompt = |text: &str| {
+        rpassword::prompt_password(text).map_err(|e| format!("Failed to read password: {}", e))
+    };
+    let password = prompt("New password: ")?;
+    if prompt("Repeat password: ")? != password {
+        return Err("Passwords don't match".into());
+    }
+    Ok(password)
+}
+
+/// Records a command-line action under the `cli` actor
+fn audit(db: &Database, action: &str, target: &User, details: serde_json::Value) {
+    let details = (!details.is_null()).then(|| details.to_string());
+    let target = format!("user:{}", target.id);
+    if let Err(e) = db.record_audit(0, "cli", action, Some(&target), details.as_deref()) {
+        tracing::warn!("Failed to record audit entry: {}", e);
+    }
+}
+
+fn stats_csv(stats: &[AccountStats]) -> String {
+    let mut out = String::from("user_id,username,games_played,best_score,total_score,updated_at");
+    for s in stats {
+        out.push_str(&format!(
+            "\n{},{},{},{},{},{}",
+            s.user_id,
+            csv_field(&s.username),
+            s.stats.games_played,
+            s.stats.best_score,
+            s.stats.total_score,
+            s.stats.updated_at.as_deref().unwrap_or(""),
+        ));
+    }
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
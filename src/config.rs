@@ -1,7 +1,17 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::db::bans::IpNet;
+
 // Game world constants
 pub const WORLD_SIZE: f64 = 4000.0;
 pub const TICK_RATE: u64 = 30; // ticks per second (30 TPS for network sanity)
 pub const TICK_DURATION_MS: u64 = 1000 / TICK_RATE;
+pub const OUTBOUND_QUEUE_LIMIT: usize = 32; // queued messages before state updates are skipped
+pub const MAX_CATCHUP_TICKS: u32 = 5; // ticks run back-to-back after a stall before the rest are dropped
+pub const TICK_REPORT_INTERVAL_SECS: u64 = 60; // how often per-phase tick timings are logged
+pub const READY_MAX_TICK_AGE_MS: u64 = 2000; // /readyz fails if the last tick is older than this
+pub const READY_DB_TIMEOUT_MS: u64 = 2000;
 
 // Player constants
 pub const STARTING_MASS: f64 = 10.0;
@@ -13,17 +23,24 @@ pub const MAX_CELLS_PER_PLAYER: usize = 16;
 pub const MERGE_TIME_SECS: f64 = 30.0;
 pub const DECAY_RATE: f64 = 0.002; // mass lost per tick for large cells
 pub const DECAY_MIN_MASS: f64 = 50.0;
+pub const ADMIN_MAX_MASS: f64 = 100_000.0; // upper bound for admin mass changes
+pub const ADMIN_MAX_SPAWN: usize = 5000; // food/viruses per admin spawn request
+pub const MAX_STEP_TICKS: u32 = 10 * TICK_RATE as u32; // ticks per console step
 
 // Split constants
 pub const SPLIT_MIN_MASS: f64 = 36.0;
 pub const SPLIT_LAUNCH_SPEED: f64 = 800.0;
 pub const SPLIT_DECEL: f64 = 0.9; // friction per tick
+pub const MAX_SPLIT_TIMES: u32 = 4; // successive splits per request; 4 turns one cell into 16
+pub const SPLIT_COOLDOWN_SECS: f64 = 0.2; // split requests closer together than this are ignored
 
 // Eject mass constants
 pub const EJECT_MASS: f64 = 14.0;
 pub const EJECT_MIN_MASS: f64 = 32.0;
 pub const EJECT_SPEED: f64 = 600.0;
 pub const EJECT_DECEL: f64 = 0.88;
+pub const EJECT_INTERVAL_SECS: f64 = 0.1; // fastest a player can eject, held or not
+pub const EJECT_SPREAD: f64 = 0.15; // max random turn of an ejected pellet, in radians
 
 // Food constants
 pub const FOOD_COUNT: usize = 500;
@@ -32,24 +49,420 @@ pub const FOOD_RADIUS: f64 = 5.0;
 
 // Virus constants
 pub const VIRUS_COUNT: usize = 15;
+#[allow(dead_code)]
 pub const VIRUS_MASS: f64 = 100.0;
 pub const VIRUS_RADIUS: f64 = 40.0;
 pub const VIRUS_SPLIT_MIN_MASS: f64 = 130.0;
 
+// Mothercell constants
+pub const MOTHERCELL_MAX_EXTRA_FOOD: usize = 300; // bursts pause while food is this far over its target
+pub const MOTHERCELL_BURST_SPREAD: f64 = 150.0; // how far past its edge a burst scatters food
+
+// Power-up constants
+pub const POWER_UP_RADIUS: f64 = 18.0;
+pub const SPEED_BOOST_MULTIPLIER: f64 = 1.6;
+pub const MAGNET_RANGE: f64 = 250.0; // how far past a cell's edge food is pulled in
+pub const MAGNET_PULL_SPEED: f64 = 400.0;
+pub const FAST_MERGE_MULTIPLIER: f64 = 4.0; // merge timers run this much faster
+
+// Bot constants
+pub const BOT_THINK_INTERVAL_MS: u64 = 100;
+pub const BOT_FLEE_DISTANCE: f64 = 300.0; // gap to a bigger cell that makes a bot run
+pub const BOT_SPLIT_RANGE: f64 = 250.0; // about how far a split cell flies
+pub const BOT_SPLIT_SAFE_DISTANCE: f64 = 600.0; // no threats this close before split-killing
+pub const BOT_VIRUS_AVOID_DISTANCE: f64 = 150.0;
+
+// Battle royale constants
+pub const ZONE_MIN_DAMAGE: f64 = 2.0; // mass per second a cell outside the zone loses at least
+pub const ZONE_KILL_MASS: f64 = 5.0; // cells shrunk below this by the zone are destroyed
+
 // Viewport
 pub const BASE_VIEWPORT_SIZE: f64 = 800.0;
 
 // Server
 pub const SERVER_PORT: u16 = 63012;
+pub const DEFAULT_ROOM: &str = "main"; // the single shared world
+pub const FRIEND_SPAWN_DISTANCE: f64 = 300.0; // how far from a friend party joins spawn
+pub const DB_POOL_SIZE: usize = 4;
+pub const DB_BUSY_TIMEOUT_MS: u64 = 5000;
 pub const SESSION_EXPIRY_HOURS: i64 = 24 * 7; // 1 week
+pub const MAX_API_TOKENS: usize = 10; // per account
+pub const MAX_API_TOKEN_NAME_LEN: usize = 40;
+pub const MAX_BROADCAST_LEN: usize = 200;
+pub const MAX_BAN_REASON_LEN: usize = 200;
+pub const MAX_BAN_DAYS: i64 = 10 * 365; // longer bans should be permanent
 pub const MAX_SKIN_SIZE: usize = 256 * 1024; // 256KB
+pub const SKIN_MAX_SOURCE_DIMENSION: u32 = 4096; // reject larger uploads before decoding
+pub const SKIN_OUTPUT_SIZE: u32 = 256; // stored skins are at most 256x256
+
+// Defaults for the command-line options
+pub const DEFAULT_SETTINGS_PATH: &str = "data/config.json";
+pub const DEFAULT_DB_PATH: &str = "data/game.db";
+pub const DEFAULT_STATIC_DIR: &str = "static";
 
 // Helper: mass to radius
 pub fn mass_to_radius(mass: f64) -> f64 {
     (mass).sqrt() * 4.0
 }
 
+// Helper: radius back to mass
+pub fn radius_to_mass(radius: f64) -> f64 {
+    (radius / 4.0).powi(2)
+}
+
 // Helper: speed based on mass
 pub fn speed_for_mass(mass: f64) -> f64 {
     BASE_SPEED / (mass).sqrt()
 }
+
+/// Operator-tunable settings loaded from the `--config` file. Anything missing
+/// from the file falls back to the defaults below.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub rate_limit: RateLimitSettings,
+    pub metrics: MetricsSettings,
+    /// Accounts given the admin role at startup and on config reload
+    pub admins: Vec<AdminAccount>,
+    pub names: NameSettings,
+    pub shutdown: ShutdownSettings,
+    pub snapshot: SnapshotSettings,
+    pub bots: BotSettings,
+    pub power_ups: PowerUpSettings,
+    pub mothercells: MothercellSettings,
+    /// Game mode of the room. Read at startup only.
+    pub mode: GameMode,
+    pub battle_royale: BattleRoyaleSettings,
+    /// Unix socket for the operator console, e.g. `data/console.sock`.
+    /// Read at startup only.
+    pub console_socket: Option<String>,
+}
+
+/// Who may scrape `GET /metrics`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsSettings {
+    /// Networks allowed without a token. Loopback only by default.
+    pub allow: Vec<IpNet>,
+    /// Scrapers sending `Authorization: Bearer <token>` are allowed from
+    /// anywhere
+    pub token: Option<String>,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        MetricsSettings {
+            allow: ["127.0.0.0/8", "::1"]
+                .iter()
+                .map(|net| net.parse().expect("valid default network"))
+                .collect(),
+            token: None,
+        }
+    }
+}
+
+/// What happens on SIGTERM / Ctrl-C
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShutdownSettings {
+    /// Warning players get before the server closes their connections
+    pub countdown_secs: u64,
+    /// How long to wait for connections to close and stats to be saved
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ShutdownSettings {
+    fn default() -> Self {
+        ShutdownSettings {
+            countdown_secs: 10,
+            drain_timeout_secs: 5,
+        }
+    }
+}
+
+/// Saving the world so a restart doesn't wipe the arena
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotSettings {
+    /// Where the snapshot is kept; `null` turns snapshots off. Read at
+    /// startup only.
+    pub path: Option<String>,
+    /// How often the world is saved while running, besides at shutdown
+    pub interval_secs: u64,
+    /// How long restored players wait for their owners to come back
+    pub reconnect_window_secs: u64,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        SnapshotSettings {
+            path: Some("data/world.json".into()),
+            interval_secs: 30,
+            reconnect_window_secs: 120,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// Endless free-for-all with respawns
+    #[default]
+    Ffa,
+    /// Rounds with a shrinking safe zone; last player alive wins
+    BattleRoyale,
+}
+
+/// Round rules for `GameMode::BattleRoyale`. Changes apply from the next
+/// round.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BattleRoyaleSettings {
+    /// Human players (bots don't count) needed in the lobby to start a round
+    pub min_players: usize,
+    /// Lobby countdown once enough players are in
+    pub countdown_secs: u64,
+    /// How long the winner is shown before the lobby opens again
+    pub results_secs: u64,
+    /// Share of its mass a cell outside the zone loses per second
+    pub zone_damage: f64,
+    /// The zone's schedule, in order. It starts covering the whole world.
+    pub zone_stages: Vec<ZoneStage>,
+}
+
+/// One step of the shrinking zone: it holds still for `wait_secs`, then
+/// shrinks to `radius` over `shrink_secs`, around a new random center
+/// inside the old circle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneStage {
+    pub wait_secs: f64,
+    pub shrink_secs: f64,
+    pub radius: f64,
+}
+
+impl Default for BattleRoyaleSettings {
+    fn default() -> Self {
+        let stage = |wait_secs, shrink_secs, radius| ZoneStage {
+            wait_secs,
+            shrink_secs,
+            radius,
+        };
+        BattleRoyaleSettings {
+            min_players: 4,
+            countdown_secs: 15,
+            results_secs: 10,
+            zone_damage: 0.1,
+            zone_stages: vec![
+                stage(30.0, 30.0, 1400.0),
+                stage(20.0, 25.0, 800.0),
+                stage(15.0, 20.0, 400.0),
+                stage(10.0, 20.0, 150.0),
+                stage(10.0, 15.0, 0.0),
+            ],
+        }
+    }
+}
+
+/// Spawning and effect length of each power-up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerUpSettings {
+    pub speed: PowerUpRule,
+    pub shield: PowerUpRule,
+    pub magnet: PowerUpRule,
+    pub merge: PowerUpRule,
+}
+
+/// One power-up appears every `spawn_secs` while fewer than `max_count`
+/// are on the map. Picking one up gives its effect for `duration_secs`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerUpRule {
+    /// 0 turns this power-up off
+    pub max_count: usize,
+    pub spawn_secs: f64,
+    pub duration_secs: f64,
+}
+
+impl Default for PowerUpSettings {
+    fn default() -> Self {
+        let rule = |max_count, spawn_secs, duration_secs| PowerUpRule {
+            max_count,
+            spawn_secs,
+            duration_secs,
+        };
+        PowerUpSettings {
+            speed: rule(4, 15.0, 8.0),
+            shield: rule(2, 30.0, 6.0),
+            magnet: rule(3, 20.0, 10.0),
+            merge: rule(3, 20.0, 15.0),
+        }
+    }
+}
+
+/// Mothercells for each game mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MothercellSettings {
+    pub ffa: MothercellRule,
+    pub battle_royale: MothercellRule,
+}
+
+impl MothercellSettings {
+    pub fn for_mode(&self, mode: GameMode) -> &MothercellRule {
+        match mode {
+            GameMode::Ffa => &self.ffa,
+            GameMode::BattleRoyale => &self.battle_royale,
+        }
+    }
+}
+
+/// Mothercells start at `start_mass` and grow by `growth_per_sec` up to
+/// `max_mass`. Every `burst_secs` one sheds up to `burst_food` of its
+/// growth as food scattered around it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MothercellRule {
+    /// How many are kept on the map; 0 turns them off
+    pub count: usize,
+    pub start_mass: f64,
+    pub max_mass: f64,
+    pub growth_per_sec: f64,
+    pub burst_secs: f64,
+    pub burst_food: usize,
+}
+
+impl Default for MothercellRule {
+    fn default() -> Self {
+        MothercellRule {
+            count: 3,
+            start_mass: 200.0,
+            max_mass: 600.0,
+            growth_per_sec: 4.0,
+            burst_secs: 5.0,
+            burst_food: 15,
+        }
+    }
+}
+
+impl Default for MothercellSettings {
+    fn default() -> Self {
+        MothercellSettings {
+            ffa: MothercellRule::default(),
+            battle_royale: MothercellRule {
+                count: 2,
+                ..MothercellRule::default()
+            },
+        }
+    }
+}
+
+/// Server-side AI players
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotSettings {
+    /// Bots are added while fewer players than this are in the room, and
+    /// removed again as people join. 0 turns bots off.
+    pub min_population: usize,
+}
+
+/// An account promoted to admin from the settings file. Both the id and
+/// the username must match, so an entry written before its account exists
+/// can't be claimed by whoever registers next.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminAccount {
+    pub id: i64,
+    pub username: String,
+}
+
+/// Rules for usernames and in-game display names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NameSettings {
+    /// Names nobody may register or play under
+    pub reserved: Vec<String>,
+    /// Words that may not appear in a name as whole words. Names are split
+    /// into words at separators, digits and camelCase, so a blocked word
+    /// inside a longer word doesn't match. Matching ignores case and
+    /// look-alike characters.
+    pub blocked_words: Vec<String>,
+}
+
+impl Default for NameSettings {
+    fn default() -> Self {
+        NameSettings {
+            reserved: ["admin", "administrator", "moderator", "mod", "server", "system", "staff"]
+                .map(String::from)
+                .to_vec(),
+            blocked_words: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    /// Login/register attempts allowed per IP per window
+    pub per_ip: u32,
+    /// Login/register attempts allowed per username from one IP per window
+    pub per_username: u32,
+    /// Attempt window. Failures are also forgotten this long after the last
+    /// failure or lockout.
+    pub window_secs: u64,
+    /// Failed logins before an IP, or a username from one IP, is locked out
+    pub lockout_after: u32,
+    /// First lockout duration; doubles with every further failure
+    pub lockout_base_secs: u64,
+    pub lockout_max_secs: u64,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        RateLimitSettings {
+            per_ip: 20,
+            per_username: 10,
+            window_secs: 60,
+            lockout_after: 5,
+            lockout_base_secs: 30,
+            lockout_max_secs: 60 * 60,
+        }
+    }
+}
+
+static SETTINGS: OnceLock<RwLock<Arc<Settings>>> = OnceLock::new();
+static SETTINGS_PATH: OnceLock<String> = OnceLock::new();
+
+fn settings_cell() -> &'static RwLock<Arc<Settings>> {
+    SETTINGS.get_or_init(|| RwLock::new(Arc::new(Settings::default())))
+}
+
+/// Current settings snapshot
+pub fn settings() -> Arc<Settings> {
+    settings_cell().read().unwrap().clone()
+}
+
+pub fn set_settings(settings: Settings) {
+    *settings_cell().write().unwrap() = Arc::new(settings);
+}
+
+/// The settings file given on the command line, re-read on config reload
+pub fn settings_path() -> &'static str {
+    SETTINGS_PATH.get().map_or(DEFAULT_SETTINGS_PATH, String::as_str)
+}
+
+/// Loads and applies the settings file at `path`, and remembers the path
+/// for later reloads. Call once at startup.
+pub fn init_settings(path: &str) -> Result<(), String> {
+    set_settings(load_settings(path)?);
+    let _ = SETTINGS_PATH.set(path.to_string());
+    Ok(())
+}
+
+/// Reads settings from a JSON file. A missing file yields the defaults.
+pub fn load_settings(path: &str) -> Result<Settings, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+        Err(e) => Err(format!("{}: {}", path, e)),
+    }
+}
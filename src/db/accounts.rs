@@ -4,35 +4,179 @@ use argon2::{
     Argon2,
 };
 use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
 use uuid::Uuid;
 use crate::config::SESSION_EXPIRY_HOURS;
+use crate::metrics::METRICS;
+use crate::db::friends::{Friend, FriendStore};
+use crate::db::skins::{SkinStatus, SkinStore};
+use crate::db::stats::{Stats, StatsStore};
+use crate::db::tokens::{ApiToken, TokenStore};
+use crate::names::{self, name_key};
+
+/// Account role. Later variants include the powers of earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Player,
+    /// Can review skins
+    Moderator,
+    /// Full control over players, the world and config
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub(super) fn parse(s: &str) -> Self {
+        match s {
+            "admin" => Role::Admin,
+            "moderator" => Role::Moderator,
+            _ => Role::Player,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct User {
     pub id: i64,
     pub username: String,
+    pub role: Role,
 }
 
-impl Database {
-    pub fn register(&self, username: &str, password: &str) -> Result<User, String> {
-        if username.len() < 2 || username.len() > 20 {
-            return Err("Username must be 2-20 characters".into());
-        }
-        if password.len() < 4 {
-            return Err("Password must be at least 4 characters".into());
+/// Everything stored about a user, as returned by `GET /api/me/export`.
+#[derive(Debug, Serialize)]
+pub struct UserExport {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+    pub created_at: Option<String>,
+    pub sessions: Vec<SessionExport>,
+    pub api_tokens: Vec<ApiToken>,
+    pub skin: Option<SkinExport>,
+    pub stats: Option<Stats>,
+    /// Friends and pending requests in both directions
+    pub friends: Vec<Friend>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionExport {
+    pub expires_at: String,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkinExport {
+    pub status: SkinStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reject_reason: Option<String>,
+    pub mime: String,
+    pub size: usize,
+    pub url: String,
+}
+
+pub(super) fn hash_password(password: &str) -> Result<String, String> {
+    let start = Instant::now();
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| format!("Hash error: {}", e));
+    METRICS.password_hash_duration.observe(start.elapsed().as_secs_f64());
+    hash
+}
+
+pub(super) fn verify_password(password: &str, hash_str: &str) -> Result<(), String> {
+    let parsed_hash =
+        PasswordHash::new(hash_str).map_err(|e| format!("Hash parse error: {}", e))?;
+    let start = Instant::now();
+    let verified = Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .map_err(|_| "Invalid username or password".to_string());
+    METRICS.password_hash_duration.observe(start.elapsed().as_secs_f64());
+    verified
+}
+
+/// A fresh session token and its expiry timestamp
+pub(super) fn new_session() -> (String, String) {
+    let token = Uuid::new_v4().to_string();
+    let expires = Utc::now() + Duration::hours(SESSION_EXPIRY_HOURS);
+    (token, expires.to_rfc3339())
+}
+
+pub(super) fn validate_password(password: &str) -> Result<(), String> {
+    if password.len() < 4 {
+        return Err("Password must be at least 4 characters".into());
+    }
+    Ok(())
+}
+
+/// Account and session storage
+pub trait AccountStore {
+    fn register(&self, username: &str, password: &str) -> Result<User, String>;
+
+    fn login(&self, username: &str, password: &str) -> Result<(User, String), String>;
+
+    fn validate_session(&self, token: &str) -> Option<User>;
+
+    fn logout(&self, token: &str);
+
+    /// Checks that the store answers queries
+    fn ping(&self) -> Result<(), String>;
+
+    fn get_user_by_id(&self, user_id: i64) -> Option<User>;
+
+    /// Finds the account whose username collides with `key`, a
+    /// [`name_key`](crate::names::name_key).
+    fn find_user_by_name_key(&self, key: &str) -> Option<User>;
+
+    fn set_role(&self, user_id: i64, role: Role) -> Result<(), String>;
+
+    /// Changes a user's password after checking the old one. Every session
+    /// except `keep_token` is invalidated.
+    fn change_password(
+        &self,
+        user_id: i64,
+        old_password: &str,
+        new_password: &str,
+        keep_token: &str,
+    ) -> Result<(), String>;
+
+    /// Sets a new password without checking the old one, and signs out
+    /// every session. For operators; players use `change_password`.
+    fn reset_password(&self, user_id: i64, new_password: &str) -> Result<(), String>;
+
+    /// Deletes a user together with their sessions, tokens, skin, stats and
+    /// friends.
+    fn delete_account(&self, user_id: i64) -> Result<(), String>;
+
+    fn export_user(&self, user_id: i64, current_token: &str) -> Option<UserExport>;
+}
+
+impl AccountStore for Database {
+    fn register(&self, username: &str, password: &str) -> Result<User, String> {
+        let username = names::validate_username(username)?;
+        validate_password(password)?;
+        let key = name_key(&username);
+        // Saves hashing for names that are obviously taken. Concurrent sign-ups
+        // for the same name are settled by the unique index on username_key.
+        if self.find_user_by_name_key(&key).is_some() {
+            return Err("Username already taken".into());
         }
 
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|e| format!("Hash error: {}", e))?
-            .to_string();
+        let hash = hash_password(password)?;
 
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn();
         conn.execute(
-            "INSERT INTO users (username, password_hash) VALUES (?1, ?2)",
-            rusqlite::params![username, hash],
+            "INSERT INTO users (username, password_hash, username_key) VALUES (?1, ?2, ?3)",
+            rusqlite::params![username, hash, key],
         )
         .map_err(|e| {
             if e.to_string().contains("UNIQUE") {
@@ -45,38 +189,38 @@ impl Database {
         let id = conn.last_insert_rowid();
         Ok(User {
             id,
-            username: username.to_string(),
+            username,
+            role: Role::Player,
         })
     }
 
-    pub fn login(&self, username: &str, password: &str) -> Result<(User, String), String> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn
-            .prepare("SELECT id, username, password_hash FROM users WHERE username = ?1")
-            .map_err(|e| format!("DB error: {}", e))?;
+    fn login(&self, username: &str, password: &str) -> Result<(User, String), String> {
+        let username = names::normalize(username);
+        let user_row = {
+            let conn = self.conn();
+            let mut stmt = conn
+                .prepare("SELECT id, username, password_hash, role FROM users WHERE username = ?1")
+                .map_err(|e| format!("DB error: {}", e))?;
 
-        let user_row = stmt
-            .query_row(rusqlite::params![username], |row| {
+            stmt.query_row(rusqlite::params![username], |row| {
                 Ok((
                     row.get::<_, i64>(0)?,
                     row.get::<_, String>(1)?,
                     row.get::<_, String>(2)?,
+                    Role::parse(&row.get::<_, String>(3)?),
                 ))
             })
-            .map_err(|_| "Invalid username or password".to_string())?;
+            .map_err(|_| "Invalid username or password".to_string())?
+        };
 
-        let (id, uname, hash_str) = user_row;
+        let (id, uname, hash_str, role) = user_row;
 
-        let parsed_hash =
-            PasswordHash::new(&hash_str).map_err(|e| format!("Hash parse error: {}", e))?;
-        Argon2::default()
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .map_err(|_| "Invalid username or password".to_string())?;
+        // Verify without holding the connection; Argon2 is deliberately slow
+        verify_password(password, &hash_str)?;
 
-        let token = Uuid::new_v4().to_string();
-        let expires = Utc::now() + Duration::hours(SESSION_EXPIRY_HOURS);
-        let expires_str = expires.to_rfc3339();
+        let (token, expires_str) = new_session();
 
+        let conn = self.conn();
         conn.execute(
             "INSERT INTO sessions (token, user_id, expires_at) VALUES (?1, ?2, ?3)",
             rusqlite::params![token, id, expires_str],
@@ -87,44 +231,235 @@ impl Database {
             User {
                 id,
                 username: uname,
+                role,
             },
             token,
         ))
     }
 
-    pub fn validate_session(&self, token: &str) -> Option<User> {
-        let conn = self.conn.lock().unwrap();
+    fn validate_session(&self, token: &str) -> Option<User> {
+        let conn = self.conn();
         let now = Utc::now().to_rfc3339();
         conn.query_row(
-            "SELECT u.id, u.username FROM sessions s JOIN users u ON s.user_id = u.id WHERE s.token = ?1 AND s.expires_at > ?2",
+            "SELECT u.id, u.username, u.role FROM sessions s JOIN users u ON s.user_id = u.id WHERE s.token = ?1 AND s.expires_at > ?2",
             rusqlite::params![token, now],
             |row| {
                 Ok(User {
                     id: row.get(0)?,
                     username: row.get(1)?,
+                    role: Role::parse(&row.get::<_, String>(2)?),
                 })
             },
         )
         .ok()
     }
 
-    pub fn logout(&self, token: &str) {
-        let conn = self.conn.lock().unwrap();
+    fn logout(&self, token: &str) {
+        let conn = self.conn();
         let _ = conn.execute("DELETE FROM sessions WHERE token = ?1", rusqlite::params![token]);
     }
 
-    pub fn get_user_by_id(&self, user_id: i64) -> Option<User> {
-        let conn = self.conn.lock().unwrap();
+    fn ping(&self) -> Result<(), String> {
+        self.conn()
+            .query_row("SELECT 1 FROM users LIMIT 1", [], |_| Ok(()))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(()),
+                e => Err(format!("DB error: {}", e)),
+            })
+    }
+
+    fn get_user_by_id(&self, user_id: i64) -> Option<User> {
+        let conn = self.conn();
         conn.query_row(
-            "SELECT id, username FROM users WHERE id = ?1",
+            "SELECT id, username, role FROM users WHERE id = ?1",
             rusqlite::params![user_id],
             |row| {
                 Ok(User {
                     id: row.get(0)?,
                     username: row.get(1)?,
+                    role: Role::parse(&row.get::<_, String>(2)?),
                 })
             },
         )
         .ok()
     }
+
+    fn find_user_by_name_key(&self, key: &str) -> Option<User> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT id, username, role FROM users WHERE username_key = ?1",
+            rusqlite::params![key],
+            |row| {
+                Ok(User {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    role: Role::parse(&row.get::<_, String>(2)?),
+                })
+            },
+        )
+        .ok()
+    }
+
+    fn set_role(&self, user_id: i64, role: Role) -> Result<(), String> {
+        let updated = self
+            .conn()
+            .execute(
+                "UPDATE users SET role = ?1 WHERE id = ?2",
+                rusqlite::params![role.as_str(), user_id],
+            )
+            .map_err(|e| format!("Database error: {}", e))?;
+        if updated == 0 {
+            return Err("User not found".into());
+        }
+        Ok(())
+    }
+
+    fn change_password(
+        &self,
+        user_id: i64,
+        old_password: &str,
+        new_password: &str,
+        keep_token: &str,
+    ) -> Result<(), String> {
+        validate_password(new_password)?;
+
+        let hash_str: String = self
+            .conn()
+            .query_row(
+                "SELECT password_hash FROM users WHERE id = ?1",
+                rusqlite::params![user_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| "User not found".to_string())?;
+        verify_password(old_password, &hash_str)
+            .map_err(|_| "Current password is incorrect".to_string())?;
+
+        let new_hash = hash_password(new_password)?;
+        let mut conn = self.conn();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Database error: {}", e))?;
+        // Only swap the hash we verified against, so a concurrent change wins cleanly
+        let updated = tx
+            .execute(
+                "UPDATE users SET password_hash = ?1 WHERE id = ?2 AND password_hash = ?3",
+                rusqlite::params![new_hash, user_id, hash_str],
+            )
+            .map_err(|e| format!("Database error: {}", e))?;
+        if updated == 0 {
+            return Err("Current password is incorrect".into());
+        }
+        tx.execute(
+            "DELETE FROM sessions WHERE user_id = ?1 AND token != ?2",
+            rusqlite::params![user_id, keep_token],
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+        tx.commit().map_err(|e| format!("Database error: {}", e))?;
+        Ok(())
+    }
+
+    fn reset_password(&self, user_id: i64, new_password: &str) -> Result<(), String> {
+        validate_password(new_password)?;
+        let new_hash = hash_password(new_password)?;
+        let mut conn = self.conn();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Database error: {}", e))?;
+        let updated = tx
+            .execute(
+                "UPDATE users SET password_hash = ?1 WHERE id = ?2",
+                rusqlite::params![new_hash, user_id],
+            )
+            .map_err(|e| format!("Database error: {}", e))?;
+        if updated == 0 {
+            return Err("User not found".into());
+        }
+        tx.execute("DELETE FROM sessions WHERE user_id = ?1", rusqlite::params![user_id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        tx.commit().map_err(|e| format!("Database error: {}", e))?;
+        Ok(())
+    }
+
+    fn delete_account(&self, user_id: i64) -> Result<(), String> {
+        let mut conn = self.conn();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Database error: {}", e))?;
+        tx.execute("DELETE FROM sessions WHERE user_id = ?1", rusqlite::params![user_id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        tx.execute("DELETE FROM stats WHERE user_id = ?1", rusqlite::params![user_id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        tx.execute(
+            "DELETE FROM skin_reports WHERE owner_id = ?1 OR reporter_id = ?1",
+            rusqlite::params![user_id],
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+        tx.execute("DELETE FROM api_tokens WHERE user_id = ?1", rusqlite::params![user_id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        tx.execute(
+            "DELETE FROM friendships WHERE requester_id = ?1 OR addressee_id = ?1",
+            rusqlite::params![user_id],
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+        let deleted = tx
+            .execute("DELETE FROM users WHERE id = ?1", rusqlite::params![user_id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        if deleted == 0 {
+            return Err("User not found".into());
+        }
+        tx.commit().map_err(|e| format!("Database error: {}", e))?;
+        Ok(())
+    }
+
+    fn export_user(&self, user_id: i64, current_token: &str) -> Option<UserExport> {
+        let user = self.get_user_by_id(user_id)?;
+        let stats = self.get_stats(user_id);
+        let api_tokens = self.list_tokens(user_id).ok()?;
+        let friends = self.friends(user_id).ok()?;
+        let reject_reason = self.skin_reject_reason(user_id);
+        let skin = self.get_skin(user_id).map(|s| SkinExport {
+            status: s.status,
+            reject_reason,
+            size: s.data.len(),
+            mime: s.mime,
+            url: format!("/api/skin/{}", user_id),
+        });
+
+        let conn = self.conn();
+        let created_at = conn
+            .query_row(
+                "SELECT created_at FROM users WHERE id = ?1",
+                rusqlite::params![user_id],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .ok()
+            .flatten();
+
+        let mut stmt = conn
+            .prepare("SELECT token, expires_at FROM sessions WHERE user_id = ?1 ORDER BY expires_at")
+            .ok()?;
+        let sessions = stmt
+            .query_map(rusqlite::params![user_id], |row| {
+                let token: String = row.get(0)?;
+                Ok(SessionExport {
+                    expires_at: row.get(1)?,
+                    current: token == current_token,
+                })
+            })
+            .ok()?
+            .filter_map(Result::ok)
+            .collect();
+
+        Some(UserExport {
+            id: user.id,
+            username: user.username,
+            role: user.role,
+            created_at,
+            sessions,
+            api_tokens,
+            skin,
+            stats,
+            friends,
+        })
+    }
 }
@@ -0,0 +1,79 @@
+use super::Database;
+use serde::Serialize;
+
+/// One privileged action, as recorded in the audit log
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Account that performed the action
+    pub actor_id: i64,
+    pub actor: String,
+    /// Short machine-readable name, e.g. `kick` or `skin_approve`
+    pub action: String,
+    /// What the action was applied to, e.g. `player:12` or `user:3`
+    pub target: Option<String>,
+    /// Free-form JSON with the action's parameters
+    pub details: Option<String>,
+    pub created_at: Option<String>,
+}
+
+/// Append-only log of moderator and admin actions
+pub trait AuditStore {
+    fn record_audit(
+        &self,
+        actor_id: i64,
+        actor: &str,
+        action: &str,
+        target: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<(), String>;
+
+    /// Most recent entries first
+    fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, String>;
+}
+
+impl AuditStore for Database {
+    fn record_audit(
+        &self,
+        actor_id: i64,
+        actor: &str,
+        action: &str,
+        target: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO audit_log (actor_id, actor, action, target, details)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![actor_id, actor, action, target, details],
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        Ok(())
+    }
+
+    fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, String> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, actor_id, actor, action, target, details, created_at
+                 FROM audit_log ORDER BY id DESC LIMIT ?1",
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        let entries = stmt
+            .query_map(rusqlite::params![limit as i64], |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    actor_id: row.get(1)?,
+                    actor: row.get(2)?,
+                    action: row.get(3)?,
+                    target: row.get(4)?,
+                    details: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("DB error: {}", e))?
+            .filter_map(Result::ok)
+            .collect();
+        Ok(entries)
+    }
+}
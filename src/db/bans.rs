@@ -0,0 +1,262 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::Database;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::messages::BanNotice;
+
+/// An IP address or network, e.g. `203.0.113.7` or `2001:db8::/32`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    /// Whether `ip` is inside this network. IPv4-mapped IPv6 addresses
+    /// match their IPv4 form.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.addr.is_ipv4() && masked(ip, self.prefix) == self.addr
+    }
+}
+
+/// Clears the host bits of `addr` beyond `prefix`
+fn masked(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(a) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((u32::from(a) & mask).into())
+        }
+        IpAddr::V6(a) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((u128::from(a) & mask).into())
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr = IpAddr::from_str(addr)
+            .map_err(|_| format!("Invalid IP address: {}", addr))?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("Invalid prefix length: /{}", p))?,
+            None => max,
+        };
+        // Store the network address, so 10.1.2.3/8 is shown as 10.0.0.0/8
+        Ok(IpNet {
+            addr: masked(addr, prefix),
+            prefix,
+        })
+    }
+}
+
+impl TryFrom<String> for IpNet {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<IpNet> for String {
+    fn from(net: IpNet) -> String {
+        net.to_string()
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = if self.addr.is_ipv4() { 32 } else { 128 };
+        if self.prefix == max {
+            write!(f, "{}", self.addr)
+        } else {
+            write!(f, "{}/{}", self.addr, self.prefix)
+        }
+    }
+}
+
+/// A ban on an account, an IP network, or both
+#[derive(Debug, Clone, Serialize)]
+pub struct Ban {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    /// Current name of the banned account, if it still exists
+    pub username: Option<String>,
+    /// Banned address or CIDR network
+    pub ip: Option<String>,
+    pub reason: String,
+    pub created_by: String,
+    pub created_at: Option<String>,
+    /// RFC 3339 timestamp; `None` for permanent bans
+    pub expires_at: Option<String>,
+}
+
+impl Ban {
+    /// Whether the ban applies to an account or a connection from `ip`
+    pub(super) fn matches(&self, user_id: Option<i64>, ip: Option<IpAddr>) -> bool {
+        let account = user_id.is_some() && self.user_id == user_id;
+        let address = match (&self.ip, ip) {
+            (Some(net), Some(ip)) => net.parse::<IpNet>().is_ok_and(|net| net.contains(ip)),
+            _ => false,
+        };
+        account || address
+    }
+
+    /// What the banned client is told
+    pub fn notice(&self) -> BanNotice {
+        let until = match &self.expires_at {
+            Some(at) => match DateTime::parse_from_rfc3339(at) {
+                Ok(at) => format!("until {}", at.with_timezone(&Utc).format("%Y-%m-%d %H:%M UTC")),
+                Err(_) => format!("until {}", at),
+            },
+            None => "permanently".to_string(),
+        };
+        BanNotice {
+            message: format!("You are banned {}: {}", until, self.reason),
+            reason: self.reason.clone(),
+            expires_at: self.expires_at.clone(),
+        }
+    }
+}
+
+/// Picks the ban that ends last: permanent bans win, then the latest expiry.
+pub(super) fn longest(bans: impl Iterator<Item = Ban>) -> Option<Ban> {
+    bans.max_by(|a, b| match (&a.expires_at, &b.expires_at) {
+        (None, None) => a.id.cmp(&b.id),
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (Some(x), Some(y)) => x.cmp(y),
+    })
+}
+
+/// Durable account and IP bans
+pub trait BanStore {
+    /// Bans an account, an IP network, or both. `expires_at` is RFC 3339;
+    /// `None` makes the ban permanent.
+    fn create_ban(
+        &self,
+        user_id: Option<i64>,
+        ip: Option<IpNet>,
+        reason: &str,
+        expires_at: Option<&str>,
+        created_by: &str,
+    ) -> Result<Ban, String>;
+
+    /// Bans that haven't expired, newest first
+    fn active_bans(&self) -> Result<Vec<Ban>, String>;
+
+    fn lift_ban(&self, ban_id: i64) -> Result<(), String>;
+
+    /// The active ban covering this account or address, if any. Callers
+    /// must refuse access on error rather than assume there is no ban.
+    fn find_ban(&self, user_id: Option<i64>, ip: Option<IpAddr>) -> Result<Option<Ban>, String>;
+}
+
+const BAN_COLUMNS: &str = "b.id, b.user_id, u.username, b.ip, b.reason, b.created_by,
+     b.created_at, b.expires_at FROM bans b LEFT JOIN users u ON u.id = b.user_id";
+
+fn ban_from_row(row: &rusqlite::Row) -> rusqlite::Result<Ban> {
+    Ok(Ban {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        username: row.get(2)?,
+        ip: row.get(3)?,
+        reason: row.get(4)?,
+        created_by: row.get(5)?,
+        created_at: row.get(6)?,
+        expires_at: row.get(7)?,
+    })
+}
+
+impl BanStore for Database {
+    fn create_ban(
+        &self,
+        user_id: Option<i64>,
+        ip: Option<IpNet>,
+        reason: &str,
+        expires_at: Option<&str>,
+        created_by: &str,
+    ) -> Result<Ban, String> {
+        if user_id.is_none() && ip.is_none() {
+            return Err("A ban needs an account or an IP".into());
+        }
+        let conn = self.conn();
+        if let Some(user_id) = user_id {
+            conn.query_row(
+                "SELECT 1 FROM users WHERE id = ?1",
+                rusqlite::params![user_id],
+                |_| Ok(()),
+            )
+            .map_err(|_| "User not found".to_string())?;
+        }
+        conn.execute(
+            "INSERT INTO bans (user_id, ip, reason, created_by, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                user_id,
+                ip.map(|ip| ip.to_string()),
+                reason,
+                created_by,
+                expires_at
+            ],
+        )
+        .map_err(|e| format!("DB error: {}", e))?;
+        let id = conn.last_insert_rowid();
+        conn.query_row(
+            &format!("SELECT {} WHERE b.id = ?1", BAN_COLUMNS),
+            rusqlite::params![id],
+            ban_from_row,
+        )
+        .map_err(|e| format!("DB error: {}", e))
+    }
+
+    fn active_bans(&self) -> Result<Vec<Ban>, String> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} WHERE b.expires_at IS NULL OR b.expires_at > ?1 ORDER BY b.id DESC",
+                BAN_COLUMNS
+            ))
+            .map_err(|e| format!("DB error: {}", e))?;
+        let bans = stmt
+            .query_map(rusqlite::params![Utc::now().to_rfc3339()], ban_from_row)
+            .map_err(|e| format!("DB error: {}", e))?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(|e| format!("DB error: {}", e))?;
+        Ok(bans)
+    }
+
+    fn lift_ban(&self, ban_id: i64) -> Result<(), String> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM bans WHERE id = ?1", rusqlite::params![ban_id])
+            .map_err(|e| format!("DB error: {}", e))?;
+        if deleted == 0 {
+            return Err("Ban not found".into());
+        }
+        Ok(())
+    }
+
+    fn find_ban(&self, user_id: Option<i64>, ip: Option<IpAddr>) -> Result<Option<Ban>, String> {
+        // CIDR matching happens in Rust; the active ban list is short
+        let bans = self.active_bans()?;
+        Ok(longest(bans.into_iter().filter(|b| b.matches(user_id, ip))))
+    }
+}
@@ -0,0 +1,170 @@
+use super::Database;
+use serde::Serialize;
+
+/// How another user relates to the one asking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FriendStatus {
+    Friends,
+    /// They sent a request we haven't answered yet
+    Incoming,
+    /// We sent a request they haven't answered yet
+    Outgoing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Friend {
+    pub user_id: i64,
+    pub username: String,
+    pub status: FriendStatus,
+}
+
+/// Friend requests and friendships
+pub trait FriendStore {
+    /// Sends a friend request to `username`. If they already asked us, the
+    /// two become friends straight away. Returns the resulting status.
+    fn request_friend(&self, user_id: i64, username: &str) -> Result<Friend, String>;
+
+    /// Accepts a pending request sent by `from_id`.
+    fn accept_friend(&self, user_id: i64, from_id: i64) -> Result<(), String>;
+
+    /// Removes a friend, declines their request or cancels our own.
+    fn remove_friend(&self, user_id: i64, other_id: i64) -> Result<(), String>;
+
+    /// Friends and pending requests in both directions, by username.
+    fn friends(&self, user_id: i64) -> Result<Vec<Friend>, String>;
+
+    fn are_friends(&self, a: i64, b: i64) -> bool;
+}
+
+impl FriendStore for Database {
+    fn request_friend(&self, user_id: i64, username: &str) -> Result<Friend, String> {
+        let conn = self.conn();
+        let (other_id, other_name): (i64, String) = conn
+            .query_row(
+                "SELECT id, username FROM users WHERE username = ?1",
+                rusqlite::params![username],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|_| "User not found".to_string())?;
+        if other_id == user_id {
+            return Err("You can't add yourself".into());
+        }
+
+        let existing: Option<(i64, String)> = conn
+            .query_row(
+                "SELECT requester_id, status FROM friendships
+                 WHERE (requester_id = ?1 AND addressee_id = ?2)
+                    OR (requester_id = ?2 AND addressee_id = ?1)",
+                rusqlite::params![user_id, other_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .ok();
+        let status = match existing {
+            Some((_, status)) if status == "accepted" => return Err("Already friends".into()),
+            Some((requester, _)) if requester == user_id => {
+                return Err("Friend request already sent".into())
+            }
+            Some(_) => {
+                conn.execute(
+                    "UPDATE friendships SET status = 'accepted'
+                     WHERE requester_id = ?1 AND addressee_id = ?2",
+                    rusqlite::params![other_id, user_id],
+                )
+                .map_err(|e| format!("DB error: {}", e))?;
+                FriendStatus::Friends
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO friendships (requester_id, addressee_id, status)
+                     VALUES (?1, ?2, 'pending')",
+                    rusqlite::params![user_id, other_id],
+                )
+                .map_err(|e| format!("DB error: {}", e))?;
+                FriendStatus::Outgoing
+            }
+        };
+        Ok(Friend {
+            user_id: other_id,
+            username: other_name,
+            status,
+        })
+    }
+
+    fn accept_friend(&self, user_id: i64, from_id: i64) -> Result<(), String> {
+        let conn = self.conn();
+        let updated = conn
+            .execute(
+                "UPDATE friendships SET status = 'accepted'
+                 WHERE requester_id = ?1 AND addressee_id = ?2 AND status = 'pending'",
+                rusqlite::params![from_id, user_id],
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        if updated == 0 {
+            return Err("No pending friend request".into());
+        }
+        Ok(())
+    }
+
+    fn remove_friend(&self, user_id: i64, other_id: i64) -> Result<(), String> {
+        let conn = self.conn();
+        let deleted = conn
+            .execute(
+                "DELETE FROM friendships
+                 WHERE (requester_id = ?1 AND addressee_id = ?2)
+                    OR (requester_id = ?2 AND addressee_id = ?1)",
+                rusqlite::params![user_id, other_id],
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        if deleted == 0 {
+            return Err("Not on your friends list".into());
+        }
+        Ok(())
+    }
+
+    fn friends(&self, user_id: i64) -> Result<Vec<Friend>, String> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT u.id, u.username, f.status, f.requester_id FROM friendships f
+                 JOIN users u ON u.id = CASE WHEN f.requester_id = ?1
+                                             THEN f.addressee_id ELSE f.requester_id END
+                 WHERE f.requester_id = ?1 OR f.addressee_id = ?1
+                 ORDER BY u.username",
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        let friends = stmt
+            .query_map(rusqlite::params![user_id], |row| {
+                let status: String = row.get(2)?;
+                let requester: i64 = row.get(3)?;
+                Ok(Friend {
+                    user_id: row.get(0)?,
+                    username: row.get(1)?,
+                    status: if status == "accepted" {
+                        FriendStatus::Friends
+                    } else if requester == user_id {
+                        FriendStatus::Outgoing
+                    } else {
+                        FriendStatus::Incoming
+                    },
+                })
+            })
+            .map_err(|e| format!("DB error: {}", e))?
+            .filter_map(Result::ok)
+            .collect();
+        Ok(friends)
+    }
+
+    fn are_friends(&self, a: i64, b: i64) -> bool {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT 1 FROM friendships
+             WHERE status = 'accepted'
+               AND ((requester_id = ?1 AND addressee_id = ?2)
+                 OR (requester_id = ?2 AND addressee_id = ?1))",
+            rusqlite::params![a, b],
+            |_| Ok(()),
+        )
+        .is_ok()
+    }
+}
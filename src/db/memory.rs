@@ -0,0 +1,740 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use chrono::Utc;
+
+use super::accounts::{
+    hash_password, new_session, validate_password, verify_password,
+    AccountStore, Role, SessionExport, SkinExport, User, UserExport,
+};
+use super::audit::{AuditEntry, AuditStore};
+use super::bans::{longest, Ban, BanStore, IpNet};
+use super::friends::{Friend, FriendStatus, FriendStore};
+use super::rounds::{RoundResult, RoundStore};
+use super::skins::{SkinData, SkinReport, SkinReview, SkinStatus, SkinStore};
+use super::stats::{AccountStats, Stats, StatsStore};
+use super::tokens::{hash_token, new_token_secret, ApiToken, TokenScope, TokenStore};
+use crate::names::{self, name_key};
+
+/// Non-persistent storage for tests and throwaway servers. Mirrors the
+/// behaviour of the SQLite [`Database`](super::Database).
+#[derive(Default)]
+pub struct MemoryStorage {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    next_user_id: i64,
+    users: BTreeMap<i64, MemUser>,
+    sessions: HashMap<String, Session>,
+    stats: HashMap<i64, Stats>,
+    reports: Vec<Report>,
+    friendships: Vec<Friendship>,
+    next_token_id: i64,
+    tokens: Vec<MemToken>,
+    audit: Vec<AuditEntry>,
+    next_ban_id: i64,
+    bans: Vec<Ban>,
+    rounds: Vec<RoundResult>,
+}
+
+struct MemUser {
+    username: String,
+    role: Role,
+    password_hash: String,
+    created_at: String,
+    skin: Option<MemSkin>,
+}
+
+struct MemSkin {
+    data: Vec<u8>,
+    mime: String,
+    status: SkinStatus,
+    reject_reason: Option<String>,
+    updated_at: String,
+}
+
+struct Session {
+    user_id: i64,
+    expires_at: String,
+}
+
+struct Report {
+    owner_id: i64,
+    reporter_id: i64,
+    reason: String,
+    created_at: String,
+}
+
+struct MemToken {
+    user_id: i64,
+    token_hash: String,
+    info: ApiToken,
+}
+
+struct Friendship {
+    requester_id: i64,
+    addressee_id: i64,
+    accepted: bool,
+}
+
+impl Friendship {
+    fn between(&self, a: i64, b: i64) -> bool {
+        (self.requester_id == a && self.addressee_id == b)
+            || (self.requester_id == b && self.addressee_id == a)
+    }
+}
+
+/// Same format as SQLite's `datetime('now')`
+fn now() -> String {
+    Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(test)]
+    pub(super) fn expire_session(&self, token: &str) {
+        if let Some(session) = self.state.lock().unwrap().sessions.get_mut(token) {
+            session.expires_at = "2000-01-01T00:00:00+00:00".into();
+        }
+    }
+}
+
+impl State {
+    fn user(&self, user_id: i64) -> Option<User> {
+        self.users.get(&user_id).map(|u| User {
+            id: user_id,
+            username: u.username.clone(),
+            role: u.role,
+        })
+    }
+
+    /// A ban with the banned account's current name filled in
+    fn ban_view(&self, ban: &Ban) -> Ban {
+        let username = ban
+            .user_id
+            .and_then(|id| self.users.get(&id))
+            .map(|u| u.username.clone());
+        Ban { username, ..ban.clone() }
+    }
+
+    /// Friends and pending requests in both directions, by username
+    fn friends(&self, user_id: i64) -> Vec<Friend> {
+        let mut friends: Vec<Friend> = self
+            .friendships
+            .iter()
+            .filter_map(|f| {
+                let (other_id, status) = if f.requester_id == user_id {
+                    (f.addressee_id, FriendStatus::Outgoing)
+                } else if f.addressee_id == user_id {
+                    (f.requester_id, FriendStatus::Incoming)
+                } else {
+                    return None;
+                };
+                Some(Friend {
+                    user_id: other_id,
+                    username: self.users.get(&other_id)?.username.clone(),
+                    status: if f.accepted { FriendStatus::Friends } else { status },
+                })
+            })
+            .collect();
+        friends.sort_by(|a, b| a.username.cmp(&b.username));
+        friends
+    }
+}
+
+impl AccountStore for MemoryStorage {
+    fn register(&self, username: &str, password: &str) -> Result<User, String> {
+        let username = names::validate_username(username)?;
+        validate_password(password)?;
+        let key = name_key(&username);
+
+        let hash = hash_password(password)?;
+
+        let mut state = self.state.lock().unwrap();
+        if state.users.values().any(|u| name_key(&u.username) == key) {
+            return Err("Username already taken".into());
+        }
+        state.next_user_id += 1;
+        let id = state.next_user_id;
+        state.users.insert(
+            id,
+            MemUser {
+                username: username.clone(),
+                role: Role::Player,
+                password_hash: hash,
+                created_at: now(),
+                skin: None,
+            },
+        );
+        Ok(User {
+            id,
+            username,
+            role: Role::Player,
+        })
+    }
+
+    fn login(&self, username: &str, password: &str) -> Result<(User, String), String> {
+        let username = names::normalize(username);
+        let (id, hash_str) = {
+            let state = self.state.lock().unwrap();
+            state
+                .users
+                .iter()
+                .find(|(_, u)| u.username == username)
+                .map(|(id, u)| (*id, u.password_hash.clone()))
+                .ok_or_else(|| "Invalid username or password".to_string())?
+        };
+
+        verify_password(password, &hash_str)?;
+
+        let (token, expires_at) = new_session();
+        let mut state = self.state.lock().unwrap();
+        state
+            .sessions
+            .insert(token.clone(), Session { user_id: id, expires_at });
+        let user = state.user(id).ok_or_else(|| "Invalid username or password".to_string())?;
+        Ok((user, token))
+    }
+
+    fn validate_session(&self, token: &str) -> Option<User> {
+        let state = self.state.lock().unwrap();
+        let session = state.sessions.get(token)?;
+        if session.expires_at <= Utc::now().to_rfc3339() {
+            return None;
+        }
+        state.user(session.user_id)
+    }
+
+    fn logout(&self, token: &str) {
+        self.state.lock().unwrap().sessions.remove(token);
+    }
+
+    fn ping(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn get_user_by_id(&self, user_id: i64) -> Option<User> {
+        self.state.lock().unwrap().user(user_id)
+    }
+
+    fn set_role(&self, user_id: i64, role: Role) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let user = state
+            .users
+            .get_mut(&user_id)
+            .ok_or_else(|| "User not found".to_string())?;
+        user.role = role;
+        Ok(())
+    }
+
+    fn find_user_by_name_key(&self, key: &str) -> Option<User> {
+        let state = self.state.lock().unwrap();
+        let (id, _) = state.users.iter().find(|(_, u)| name_key(&u.username) == key)?;
+        state.user(*id)
+    }
+
+    fn change_password(
+        &self,
+        user_id: i64,
+        old_password: &str,
+        new_password: &str,
+        keep_token: &str,
+    ) -> Result<(), String> {
+        validate_password(new_password)?;
+
+        let hash_str = self
+            .state
+            .lock()
+            .unwrap()
+            .users
+            .get(&user_id)
+            .map(|u| u.password_hash.clone())
+            .ok_or_else(|| "User not found".to_string())?;
+        verify_password(old_password, &hash_str)
+            .map_err(|_| "Current password is incorrect".to_string())?;
+
+        let new_hash = hash_password(new_password)?;
+        let mut state = self.state.lock().unwrap();
+        match state.users.get_mut(&user_id) {
+            Some(user) if user.password_hash == hash_str => user.password_hash = new_hash,
+            Some(_) => return Err("Current password is incorrect".into()),
+            None => return Err("User not found".into()),
+        }
+        state
+            .sessions
+            .retain(|token, s| s.user_id != user_id || token == keep_token);
+        Ok(())
+    }
+
+    fn reset_password(&self, user_id: i64, new_password: &str) -> Result<(), String> {
+        validate_password(new_password)?;
+        let new_hash = hash_password(new_password)?;
+        let mut state = self.state.lock().unwrap();
+        let user = state
+            .users
+            .get_mut(&user_id)
+            .ok_or_else(|| "User not found".to_string())?;
+        user.password_hash = new_hash;
+        state.sessions.retain(|_, s| s.user_id != user_id);
+        Ok(())
+    }
+
+    fn delete_account(&self, user_id: i64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.users.remove(&user_id).is_none() {
+            return Err("User not found".into());
+        }
+        state.sessions.retain(|_, s| s.user_id != user_id);
+        state.tokens.retain(|t| t.user_id != user_id);
+        state.stats.remove(&user_id);
+        state
+            .reports
+            .retain(|r| r.owner_id != user_id && r.reporter_id != user_id);
+        state
+            .friendships
+            .retain(|f| f.requester_id != user_id && f.addressee_id != user_id);
+        Ok(())
+    }
+
+    fn export_user(&self, user_id: i64, current_token: &str) -> Option<UserExport> {
+        let state = self.state.lock().unwrap();
+        let user = state.users.get(&user_id)?;
+        let mut sessions: Vec<SessionExport> = state
+            .sessions
+            .iter()
+            .filter(|(_, s)| s.user_id == user_id)
+            .map(|(token, s)| SessionExport {
+                expires_at: s.expires_at.clone(),
+                current: token == current_token,
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.expires_at.cmp(&b.expires_at));
+
+        Some(UserExport {
+            id: user_id,
+            username: user.username.clone(),
+            role: user.role,
+            created_at: Some(user.created_at.clone()),
+            sessions,
+            api_tokens: state
+                .tokens
+                .iter()
+                .filter(|t| t.user_id == user_id)
+                .map(|t| t.info.clone())
+                .collect(),
+            skin: user.skin.as_ref().map(|s| SkinExport {
+                status: s.status,
+                reject_reason: s.reject_reason.clone(),
+                mime: s.mime.clone(),
+                size: s.data.len(),
+                url: format!("/api/skin/{}", user_id),
+            }),
+            stats: state.stats.get(&user_id).cloned(),
+            friends: state.friends(user_id),
+        })
+    }
+}
+
+impl SkinStore for MemoryStorage {
+    fn set_skin(&self, user_id: i64, data: &[u8], mime: &str) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let user = state
+            .users
+            .get_mut(&user_id)
+            .ok_or_else(|| "Failed to save skin: no such user".to_string())?;
+        user.skin = Some(MemSkin {
+            data: data.to_vec(),
+            mime: mime.to_string(),
+            status: SkinStatus::Pending,
+            reject_reason: None,
+            updated_at: now(),
+        });
+        state.reports.retain(|r| r.owner_id != user_id);
+        Ok(())
+    }
+
+    fn get_skin(&self, user_id: i64) -> Option<SkinData> {
+        let state = self.state.lock().unwrap();
+        let skin = state.users.get(&user_id)?.skin.as_ref()?;
+        Some(SkinData {
+            data: skin.data.clone(),
+            mime: skin.mime.clone(),
+            status: skin.status,
+        })
+    }
+
+    fn skin_status(&self, user_id: i64) -> Option<SkinStatus> {
+        let state = self.state.lock().unwrap();
+        state.users.get(&user_id)?.skin.as_ref().map(|s| s.status)
+    }
+
+    fn skin_reject_reason(&self, user_id: i64) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        let skin = state.users.get(&user_id)?.skin.as_ref()?;
+        skin.reject_reason.clone().filter(|_| skin.status == SkinStatus::Rejected)
+    }
+
+    fn skin_queue(&self) -> Result<Vec<SkinReview>, String> {
+        let state = self.state.lock().unwrap();
+        let mut queue: Vec<SkinReview> = state
+            .users
+            .iter()
+            .filter_map(|(id, u)| {
+                let skin = u.skin.as_ref()?;
+                let reported = state.reports.iter().any(|r| r.owner_id == *id);
+                let queued = skin.status == SkinStatus::Pending
+                    || (skin.status == SkinStatus::Approved && reported);
+                if !queued {
+                    return None;
+                }
+                Some(SkinReview {
+                    user_id: *id,
+                    username: u.username.clone(),
+                    status: skin.status,
+                    updated_at: Some(skin.updated_at.clone()),
+                    reports: state
+                        .reports
+                        .iter()
+                        .filter(|r| r.owner_id == *id)
+                        .map(|r| SkinReport {
+                            reporter: state
+                                .users
+                                .get(&r.reporter_id)
+                                .map(|u| u.username.clone())
+                                .unwrap_or_default(),
+                            reason: r.reason.clone(),
+                            created_at: Some(r.created_at.clone()),
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+        queue.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+        Ok(queue)
+    }
+
+    fn review_skin(
+        &self,
+        user_id: i64,
+        status: SkinStatus,
+        reason: Option<&str>,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let skin = state
+            .users
+            .get_mut(&user_id)
+            .and_then(|u| u.skin.as_mut())
+            .ok_or_else(|| "No skin found".to_string())?;
+        skin.status = status;
+        skin.reject_reason = reason.map(str::to_string);
+        state.reports.retain(|r| r.owner_id != user_id);
+        Ok(())
+    }
+
+    fn report_skin(&self, owner_id: i64, reporter_id: i64, reason: &str) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .reports
+            .retain(|r| !(r.owner_id == owner_id && r.reporter_id == reporter_id));
+        state.reports.push(Report {
+            owner_id,
+            reporter_id,
+            reason: reason.to_string(),
+            created_at: now(),
+        });
+        Ok(())
+    }
+}
+
+impl StatsStore for MemoryStorage {
+    fn record_game(&self, user_id: i64, score: u64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if !state.users.contains_key(&user_id) {
+            return Ok(());
+        }
+        let stats = state.stats.entry(user_id).or_insert(Stats {
+            games_played: 0,
+            best_score: 0,
+            total_score: 0,
+            updated_at: None,
+        });
+        stats.games_played += 1;
+        stats.best_score = stats.best_score.max(score as i64);
+        stats.total_score += score as i64;
+        stats.updated_at = Some(now());
+        Ok(())
+    }
+
+    fn get_stats(&self, user_id: i64) -> Option<Stats> {
+        self.state.lock().unwrap().stats.get(&user_id).cloned()
+    }
+
+    fn all_stats(&self) -> Result<Vec<AccountStats>, String> {
+        let state = self.state.lock().unwrap();
+        let mut all: Vec<AccountStats> = state
+            .stats
+            .iter()
+            .filter_map(|(id, stats)| {
+                Some(AccountStats {
+                    user_id: *id,
+                    username: state.users.get(id)?.username.clone(),
+                    stats: stats.clone(),
+                })
+            })
+            .collect();
+        all.sort_by(|a, b| {
+            b.stats
+                .best_score
+                .cmp(&a.stats.best_score)
+                .then(a.user_id.cmp(&b.user_id))
+        });
+        Ok(all)
+    }
+}
+
+impl FriendStore for MemoryStorage {
+    fn request_friend(&self, user_id: i64, username: &str) -> Result<Friend, String> {
+        let mut state = self.state.lock().unwrap();
+        let other_id = state
+            .users
+            .iter()
+            .find(|(_, u)| u.username == username)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| "User not found".to_string())?;
+        if other_id == user_id {
+            return Err("You can't add yourself".into());
+        }
+
+        let status = match state.friendships.iter_mut().find(|f| f.between(user_id, other_id)) {
+            Some(f) if f.accepted => return Err("Already friends".into()),
+            Some(f) if f.requester_id == user_id => {
+                return Err("Friend request already sent".into())
+            }
+            Some(f) => {
+                f.accepted = true;
+                FriendStatus::Friends
+            }
+            None => {
+                state.friendships.push(Friendship {
+                    requester_id: user_id,
+                    addressee_id: other_id,
+                    accepted: false,
+                });
+                FriendStatus::Outgoing
+            }
+        };
+        Ok(Friend {
+            user_id: other_id,
+            username: username.to_string(),
+            status,
+        })
+    }
+
+    fn accept_friend(&self, user_id: i64, from_id: i64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let request = state
+            .friendships
+            .iter_mut()
+            .find(|f| f.requester_id == from_id && f.addressee_id == user_id && !f.accepted)
+            .ok_or_else(|| "No pending friend request".to_string())?;
+        request.accepted = true;
+        Ok(())
+    }
+
+    fn remove_friend(&self, user_id: i64, other_id: i64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.friendships.len();
+        state.friendships.retain(|f| !f.between(user_id, other_id));
+        if state.friendships.len() == before {
+            return Err("Not on your friends list".into());
+        }
+        Ok(())
+    }
+
+    fn friends(&self, user_id: i64) -> Result<Vec<Friend>, String> {
+        Ok(self.state.lock().unwrap().friends(user_id))
+    }
+
+    fn are_friends(&self, a: i64, b: i64) -> bool {
+        let state = self.state.lock().unwrap();
+        state.friendships.iter().any(|f| f.accepted && f.between(a, b))
+    }
+}
+
+impl TokenStore for MemoryStorage {
+    fn create_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scopes: &[TokenScope],
+    ) -> Result<(ApiToken, String), String> {
+        let secret = new_token_secret();
+        let mut state = self.state.lock().unwrap();
+        state.next_token_id += 1;
+        let info = ApiToken {
+            id: state.next_token_id,
+            name: name.to_string(),
+            scopes: scopes.to_vec(),
+            created_at: Some(now()),
+            last_used_at: None,
+        };
+        state.tokens.push(MemToken {
+            user_id,
+            token_hash: hash_token(&secret),
+            info: info.clone(),
+        });
+        Ok((info, secret))
+    }
+
+    fn list_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, String> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .tokens
+            .iter()
+            .filter(|t| t.user_id == user_id)
+            .map(|t| t.info.clone())
+            .collect())
+    }
+
+    fn revoke_token(&self, user_id: i64, token_id: i64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.tokens.len();
+        state
+            .tokens
+            .retain(|t| !(t.user_id == user_id && t.info.id == token_id));
+        if state.tokens.len() == before {
+            return Err("Token not found".into());
+        }
+        Ok(())
+    }
+
+    fn validate_token(&self, secret: &str) -> Option<(User, Vec<TokenScope>)> {
+        let hash = hash_token(secret);
+        let mut state = self.state.lock().unwrap();
+        let token = state.tokens.iter_mut().find(|t| t.token_hash == hash)?;
+        token.info.last_used_at = Some(now());
+        let (user_id, scopes) = (token.user_id, token.info.scopes.clone());
+        state.user(user_id).map(|user| (user, scopes))
+    }
+}
+
+impl AuditStore for MemoryStorage {
+    fn record_audit(
+        &self,
+        actor_id: i64,
+        actor: &str,
+        action: &str,
+        target: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.audit.len() as i64 + 1;
+        state.audit.push(AuditEntry {
+            id,
+            actor_id,
+            actor: actor.to_string(),
+            action: action.to_string(),
+            target: target.map(str::to_string),
+            details: details.map(str::to_string),
+            created_at: Some(now()),
+        });
+        Ok(())
+    }
+
+    fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, String> {
+        let state = self.state.lock().unwrap();
+        Ok(state.audit.iter().rev().take(limit).cloned().collect())
+    }
+}
+
+impl RoundStore for MemoryStorage {
+    fn record_round(
+        &self,
+        started_at: &str,
+        players: usize,
+        winner_user_id: Option<i64>,
+        winner_name: Option<&str>,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.rounds.len() as i64 + 1;
+        state.rounds.push(RoundResult {
+            id,
+            started_at: started_at.to_string(),
+            ended_at: Some(now()),
+            players: players as i64,
+            winner_user_id,
+            winner_name: winner_name.map(str::to_string),
+        });
+        Ok(())
+    }
+
+    fn recent_rounds(&self, limit: usize) -> Result<Vec<RoundResult>, String> {
+        let state = self.state.lock().unwrap();
+        Ok(state.rounds.iter().rev().take(limit).cloned().collect())
+    }
+}
+
+impl BanStore for MemoryStorage {
+    fn create_ban(
+        &self,
+        user_id: Option<i64>,
+        ip: Option<IpNet>,
+        reason: &str,
+        expires_at: Option<&str>,
+        created_by: &str,
+    ) -> Result<Ban, String> {
+        if user_id.is_none() && ip.is_none() {
+            return Err("A ban needs an account or an IP".into());
+        }
+        let mut state = self.state.lock().unwrap();
+        if user_id.is_some_and(|id| !state.users.contains_key(&id)) {
+            return Err("User not found".into());
+        }
+        state.next_ban_id += 1;
+        let ban = Ban {
+            id: state.next_ban_id,
+            user_id,
+            username: None,
+            ip: ip.map(|ip| ip.to_string()),
+            reason: reason.to_string(),
+            created_by: created_by.to_string(),
+            created_at: Some(now()),
+            expires_at: expires_at.map(str::to_string),
+        };
+        state.bans.push(ban);
+        Ok(state.ban_view(state.bans.last().unwrap()))
+    }
+
+    fn active_bans(&self) -> Result<Vec<Ban>, String> {
+        let state = self.state.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+        Ok(state
+            .bans
+            .iter()
+            .rev()
+            .filter(|b| b.expires_at.as_ref().is_none_or(|at| *at > now))
+            .map(|b| state.ban_view(b))
+            .collect())
+    }
+
+    fn lift_ban(&self, ban_id: i64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.bans.len();
+        state.bans.retain(|b| b.id != ban_id);
+        if state.bans.len() == before {
+            return Err("Ban not found".into());
+        }
+        Ok(())
+    }
+
+    fn find_ban(&self, user_id: Option<i64>, ip: Option<IpAddr>) -> Result<Option<Ban>, String> {
+        let bans = self.active_bans()?;
+        Ok(longest(bans.into_iter().filter(|b| b.matches(user_id, ip))))
+    }
+}
@@ -1,21 +1,177 @@
 pub mod schema;
 pub mod accounts;
+pub mod audit;
+pub mod bans;
+pub mod friends;
+pub mod memory;
+pub mod rounds;
 pub mod skins;
+pub mod stats;
+pub mod tokens;
+
+#[cfg(test)]
+mod tests;
 
 use rusqlite::Connection;
-use std::sync::Mutex;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::{DB_BUSY_TIMEOUT_MS, DB_POOL_SIZE};
+use crate::metrics::METRICS;
+use accounts::AccountStore;
+use audit::AuditStore;
+use bans::BanStore;
+use friends::FriendStore;
+use rounds::RoundStore;
+use skins::SkinStore;
+use stats::StatsStore;
+use tokens::TokenStore;
+
+/// Everything the server persists. Implemented by the SQLite [`Database`]
+/// and by [`memory::MemoryStorage`].
+pub trait Storage:
+    AccountStore
+    + AuditStore
+    + BanStore
+    + FriendStore
+    + RoundStore
+    + SkinStore
+    + StatsStore
+    + TokenStore
+    + Send
+    + Sync
+{
+}
 
+impl<T> Storage for T where
+    T: AccountStore
+        + AuditStore
+        + BanStore
+        + FriendStore
+        + RoundStore
+        + SkinStore
+        + StatsStore
+        + TokenStore
+        + Send
+        + Sync
+{
+}
+
+pub type SharedDb = Arc<dyn Storage>;
+
+impl dyn Storage {
+    /// Runs blocking storage work on Tokio's blocking thread pool so it
+    /// never stalls the game's async tasks.
+    pub async fn run<F, T>(self: &Arc<Self>, f: F) -> T
+    where
+        F: FnOnce(&dyn Storage) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        match tokio::task::spawn_blocking(move || f(&*db)).await {
+            Ok(result) => result,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+}
+
+/// SQLite storage backed by a small pool of connections. All methods are
+/// blocking; async code should go through [`Storage::run`](dyn Storage::run).
 pub struct Database {
-    pub conn: Mutex<Connection>,
+    pool: Mutex<Vec<Connection>>,
+    available: Condvar,
+}
+
+/// A connection checked out of the pool; returned on drop. The time it was
+/// held is recorded as query time.
+pub struct PooledConnection<'a> {
+    db: &'a Database,
+    conn: Option<Connection>,
+    checked_out: Instant,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            METRICS
+                .db_duration
+                .observe(self.checked_out.elapsed().as_secs_f64());
+            self.db.pool.lock().unwrap().push(conn);
+            self.db.available.notify_one();
+        }
+    }
+}
+
+fn open_connection(path: &str) -> Connection {
+    let conn = Connection::open(path).expect("Failed to open database");
+    conn.busy_timeout(Duration::from_millis(DB_BUSY_TIMEOUT_MS))
+        .expect("Failed to set busy timeout");
+    conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+        .expect("Failed to configure database");
+    conn
 }
 
 impl Database {
     pub fn new(path: &str) -> Self {
-        let conn = Connection::open(path).expect("Failed to open database");
+        // Every in-memory connection would be its own database
+        let size = if path == ":memory:" { 1 } else { DB_POOL_SIZE.max(1) };
+        let conns = (0..size).map(|_| open_connection(path)).collect();
         let db = Database {
-            conn: Mutex::new(conn),
+            pool: Mutex::new(conns),
+            available: Condvar::new(),
         };
         schema::initialize(&db);
         db
     }
+
+    /// Checks out a connection, blocking until one is free.
+    pub fn conn(&self) -> PooledConnection<'_> {
+        let start = Instant::now();
+        let mut pool = self.pool.lock().unwrap();
+        loop {
+            if let Some(conn) = pool.pop() {
+                METRICS.db_pool_wait.observe(start.elapsed().as_secs_f64());
+                return PooledConnection {
+                    db: self,
+                    conn: Some(conn),
+                    checked_out: Instant::now(),
+                };
+            }
+            pool = self.available.wait(pool).unwrap();
+        }
+    }
+
+    /// Rebuilds the database file to reclaim free pages.
+    pub fn vacuum(&self) -> Result<(), String> {
+        self.conn()
+            .execute_batch("VACUUM")
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Writes a consistent copy of the database to `path`, which must not
+    /// exist yet. Safe to run while the server is using the database.
+    pub fn backup(&self, path: &str) -> Result<(), String> {
+        if std::path::Path::new(path).exists() {
+            return Err(format!("{} already exists", path));
+        }
+        self.conn()
+            .execute("VACUUM INTO ?1", rusqlite::params![path])
+            .map(|_| ())
+            .map_err(|e| format!("Database error: {}", e))
+    }
 }
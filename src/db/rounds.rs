@@ -0,0 +1,76 @@
+use super::Database;
+use serde::Serialize;
+
+/// A finished battle royale round
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundResult {
+    pub id: i64,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    /// Players alive when the round started
+    pub players: i64,
+    /// Account of the winner, if they were logged in
+    pub winner_user_id: Option<i64>,
+    /// `None` when the last players died together
+    pub winner_name: Option<String>,
+}
+
+/// Battle royale round results
+pub trait RoundStore {
+    /// Records a finished round. `started_at` uses SQLite's
+    /// `datetime('now')` format.
+    fn record_round(
+        &self,
+        started_at: &str,
+        players: usize,
+        winner_user_id: Option<i64>,
+        winner_name: Option<&str>,
+    ) -> Result<(), String>;
+
+    /// Most recent rounds first
+    fn recent_rounds(&self, limit: usize) -> Result<Vec<RoundResult>, String>;
+}
+
+impl RoundStore for Database {
+    fn record_round(
+        &self,
+        started_at: &str,
+        players: usize,
+        winner_user_id: Option<i64>,
+        winner_name: Option<&str>,
+    ) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO rounds (started_at, players, winner_user_id, winner_name)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![started_at, players as i64, winner_user_id, winner_name],
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        Ok(())
+    }
+
+    fn recent_rounds(&self, limit: usize) -> Result<Vec<RoundResult>, String> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, started_at, ended_at, players, winner_user_id, winner_name
+                 FROM rounds ORDER BY id DESC LIMIT ?1",
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        let rounds = stmt
+            .query_map(rusqlite::params![limit as i64], |row| {
+                Ok(RoundResult {
+                    id: row.get(0)?,
+                    started_at: row.get(1)?,
+                    ended_at: row.get(2)?,
+                    players: row.get(3)?,
+                    winner_user_id: row.get(4)?,
+                    winner_name: row.get(5)?,
+                })
+            })
+            .map_err(|e| format!("DB error: {}", e))?
+            .filter_map(Result::ok)
+            .collect();
+        Ok(rounds)
+    }
+}
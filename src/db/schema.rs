@@ -1,7 +1,9 @@
 use super::Database;
+use rusqlite::Connection;
+use crate::names::name_key;
 
 pub fn initialize(db: &Database) {
-    let conn = db.conn.lock().unwrap();
+    let conn = db.conn();
     conn.execute_batch(
         "
         CREATE TABLE IF NOT EXISTS users (
@@ -18,7 +20,175 @@ pub fn initialize(db: &Database) {
             user_id    INTEGER NOT NULL REFERENCES users(id),
             expires_at TEXT    NOT NULL
         );
+
+        CREATE TABLE IF NOT EXISTS stats (
+            user_id      INTEGER PRIMARY KEY REFERENCES users(id),
+            games_played INTEGER NOT NULL DEFAULT 0,
+            best_score   INTEGER NOT NULL DEFAULT 0,
+            total_score  INTEGER NOT NULL DEFAULT 0,
+            updated_at   TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS skin_reports (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            owner_id    INTEGER NOT NULL REFERENCES users(id),
+            reporter_id INTEGER NOT NULL REFERENCES users(id),
+            reason      TEXT    NOT NULL,
+            created_at  TEXT    DEFAULT (datetime('now')),
+            UNIQUE (owner_id, reporter_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS friendships (
+            requester_id INTEGER NOT NULL REFERENCES users(id),
+            addressee_id INTEGER NOT NULL REFERENCES users(id),
+            status       TEXT    NOT NULL DEFAULT 'pending',
+            created_at   TEXT    DEFAULT (datetime('now')),
+            PRIMARY KEY (requester_id, addressee_id)
+        );
+        CREATE INDEX IF NOT EXISTS friendships_addressee ON friendships(addressee_id);
+
+        CREATE TABLE IF NOT EXISTS api_tokens (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id      INTEGER NOT NULL REFERENCES users(id),
+            name         TEXT    NOT NULL,
+            token_hash   TEXT    NOT NULL UNIQUE,
+            scopes       TEXT    NOT NULL,
+            created_at   TEXT    DEFAULT (datetime('now')),
+            last_used_at TEXT
+        );
+
+        -- Actor name is copied so entries survive account deletion
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            actor_id   INTEGER NOT NULL,
+            actor      TEXT    NOT NULL,
+            action     TEXT    NOT NULL,
+            target     TEXT,
+            details    TEXT,
+            created_at TEXT    DEFAULT (datetime('now'))
+        );
+
+        -- Account and/or IP bans; expires_at is RFC 3339, NULL = permanent
+        CREATE TABLE IF NOT EXISTS bans (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id    INTEGER,
+            ip         TEXT,
+            reason     TEXT    NOT NULL,
+            created_by TEXT    NOT NULL,
+            created_at TEXT    DEFAULT (datetime('now')),
+            expires_at TEXT
+        );
+        CREATE INDEX IF NOT EXISTS bans_user ON bans(user_id);
+
+        -- Battle royale results; the winner's name is copied like audit actors
+        CREATE TABLE IF NOT EXISTS rounds (
+            id             INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at     TEXT    NOT NULL,
+            ended_at       TEXT    DEFAULT (datetime('now')),
+            players        INTEGER NOT NULL,
+            winner_user_id INTEGER,
+            winner_name    TEXT
+        );
         ",
     )
     .expect("Failed to initialize database schema");
+
+    // Skins uploaded before moderation existed stay live
+    if add_column(&conn, "users", "skin_status", "TEXT") {
+        conn.execute(
+            "UPDATE users SET skin_status = 'approved' WHERE skin_blob IS NOT NULL",
+            [],
+        )
+        .expect("Failed to migrate skin status");
+    }
+    add_column(&conn, "users", "skin_reject_reason", "TEXT");
+    add_column(&conn, "users", "skin_updated_at", "TEXT");
+
+    // Case- and confusable-folded username, for collision checks
+    if add_column(&conn, "users", "username_key", "TEXT") {
+        let users: Vec<(i64, String)> = conn
+            .prepare("SELECT id, username FROM users")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect()
+            })
+            .expect("Failed to migrate username keys");
+        for (id, username) in users {
+            conn.execute(
+                "UPDATE users SET username_key = ?1 WHERE id = ?2",
+                rusqlite::params![name_key(&username), id],
+            )
+            .expect("Failed to migrate username keys");
+        }
+    }
+    add_column(&conn, "users", "role", "TEXT NOT NULL DEFAULT 'player'");
+    make_username_key_unique(&conn);
+
+    // Bans created with absurd durations got expiries past year 9999
+    // ("+10000-..."), which compare as already expired. They were meant to
+    // last forever.
+    conn.execute("UPDATE bans SET expires_at = NULL WHERE expires_at LIKE '+%'", [])
+        .expect("Failed to migrate ban expiries");
+}
+
+/// The username key index used to be non-unique, which left `register`
+/// open to two sign-ups racing for the same name. Where older databases
+/// hold colliding names, the oldest account keeps the key and the others
+/// are left without one; they can still log in but no longer block
+/// the name.
+fn make_username_key_unique(conn: &Connection) {
+    let unique: Option<bool> = conn
+        .query_row(
+            "SELECT \"unique\" FROM pragma_index_list('users') WHERE name = 'users_username_key'",
+            [],
+            |row| row.get(0),
+        )
+        .ok();
+    if unique == Some(true) {
+        return;
+    }
+
+    let duplicates: Vec<(i64, String)> = conn
+        .prepare(
+            "SELECT id, username FROM users u
+             WHERE EXISTS (SELECT 1 FROM users o
+                           WHERE o.username_key = u.username_key AND o.id < u.id)",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .expect("Failed to migrate username keys");
+    for (id, username) in duplicates {
+        tracing::warn!(
+            "Username {:?} (id {}) collides with an older account; it no longer reserves its name",
+            username,
+            id
+        );
+        conn.execute(
+            "UPDATE users SET username_key = NULL WHERE id = ?1",
+            rusqlite::params![id],
+        )
+        .expect("Failed to migrate username keys");
+    }
+
+    conn.execute_batch(
+        "DROP INDEX IF EXISTS users_username_key;
+         CREATE UNIQUE INDEX users_username_key ON users(username_key);",
+    )
+    .expect("Failed to initialize database schema");
+}
+
+/// Adds a column to an existing table. Returns true if it was missing.
+fn add_column(conn: &Connection, table: &str, column: &str, decl: &str) -> bool {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))
+        .and_then(|mut stmt| stmt.exists(rusqlite::params![column]))
+        .expect("Failed to inspect database schema");
+    if exists {
+        return false;
+    }
+    conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))
+        .expect("Failed to migrate database schema");
+    true
 }
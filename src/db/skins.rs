@@ -1,44 +1,220 @@
 use super::Database;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkinStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl SkinStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkinStatus::Pending => "pending",
+            SkinStatus::Approved => "approved",
+            SkinStatus::Rejected => "rejected",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "approved" => SkinStatus::Approved,
+            "rejected" => SkinStatus::Rejected,
+            _ => SkinStatus::Pending,
+        }
+    }
+}
 
 pub struct SkinData {
     pub data: Vec<u8>,
     pub mime: String,
+    pub status: SkinStatus,
+}
+
+/// A skin waiting for a moderator: either freshly uploaded or reported.
+#[derive(Debug, Serialize)]
+pub struct SkinReview {
+    pub user_id: i64,
+    pub username: String,
+    pub status: SkinStatus,
+    pub updated_at: Option<String>,
+    pub reports: Vec<SkinReport>,
 }
 
-impl Database {
-    pub fn set_skin(&self, user_id: i64, data: &[u8], mime: &str) -> Result<(), String> {
-        let conn = self.conn.lock().unwrap();
+#[derive(Debug, Serialize)]
+pub struct SkinReport {
+    pub reporter: String,
+    pub reason: String,
+    pub created_at: Option<String>,
+}
+
+/// Skin storage and moderation
+pub trait SkinStore {
+    /// Stores a new skin. It stays pending until a moderator approves it.
+    fn set_skin(&self, user_id: i64, data: &[u8], mime: &str) -> Result<(), String>;
+
+    fn get_skin(&self, user_id: i64) -> Option<SkinData>;
+
+    fn skin_status(&self, user_id: i64) -> Option<SkinStatus>;
+
+    /// Moderator's reason for rejecting the current skin, if any
+    fn skin_reject_reason(&self, user_id: i64) -> Option<String>;
+
+    /// Pending skins plus approved skins that players have reported.
+    fn skin_queue(&self) -> Result<Vec<SkinReview>, String>;
+
+    /// Approves or rejects a user's current skin and closes its reports.
+    fn review_skin(
+        &self,
+        user_id: i64,
+        status: SkinStatus,
+        reason: Option<&str>,
+    ) -> Result<(), String>;
+
+    fn report_skin(&self, owner_id: i64, reporter_id: i64, reason: &str) -> Result<(), String>;
+}
+
+impl SkinStore for Database {
+    fn set_skin(&self, user_id: i64, data: &[u8], mime: &str) -> Result<(), String> {
+        let conn = self.conn();
         conn.execute(
-            "UPDATE users SET skin_blob = ?1, skin_mime = ?2 WHERE id = ?3",
+            "UPDATE users SET skin_blob = ?1, skin_mime = ?2, skin_status = 'pending',
+                skin_reject_reason = NULL, skin_updated_at = datetime('now')
+             WHERE id = ?3",
             rusqlite::params![data, mime, user_id],
         )
         .map_err(|e| format!("Failed to save skin: {}", e))?;
+        conn.execute(
+            "DELETE FROM skin_reports WHERE owner_id = ?1",
+            rusqlite::params![user_id],
+        )
+        .map_err(|e| format!("Failed to save skin: {}", e))?;
         Ok(())
     }
 
-    pub fn get_skin(&self, user_id: i64) -> Option<SkinData> {
-        let conn = self.conn.lock().unwrap();
+    fn get_skin(&self, user_id: i64) -> Option<SkinData> {
+        let conn = self.conn();
         conn.query_row(
-            "SELECT skin_blob, skin_mime FROM users WHERE id = ?1 AND skin_blob IS NOT NULL",
+            "SELECT skin_blob, skin_mime, skin_status FROM users WHERE id = ?1 AND skin_blob IS NOT NULL",
             rusqlite::params![user_id],
             |row| {
                 Ok(SkinData {
                     data: row.get(0)?,
                     mime: row.get(1)?,
+                    status: SkinStatus::parse(&row.get::<_, Option<String>>(2)?.unwrap_or_default()),
                 })
             },
         )
         .ok()
     }
 
-    pub fn has_skin(&self, user_id: i64) -> bool {
-        let conn = self.conn.lock().unwrap();
+    fn skin_status(&self, user_id: i64) -> Option<SkinStatus> {
+        let conn = self.conn();
         conn.query_row(
-            "SELECT COUNT(*) FROM users WHERE id = ?1 AND skin_blob IS NOT NULL",
+            "SELECT skin_status FROM users WHERE id = ?1 AND skin_blob IS NOT NULL",
             rusqlite::params![user_id],
-            |row| row.get::<_, i64>(0),
+            |row| row.get::<_, Option<String>>(0),
         )
-        .unwrap_or(0)
-            > 0
+        .ok()
+        .map(|s| SkinStatus::parse(&s.unwrap_or_default()))
+    }
+
+    fn skin_reject_reason(&self, user_id: i64) -> Option<String> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT skin_reject_reason FROM users WHERE id = ?1 AND skin_status = 'rejected'",
+            rusqlite::params![user_id],
+            |row| row.get(0),
+        )
+        .ok()
+        .flatten()
+    }
+
+    fn skin_queue(&self) -> Result<Vec<SkinReview>, String> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, username, skin_status, skin_updated_at FROM users
+                 WHERE skin_blob IS NOT NULL
+                   AND (skin_status = 'pending'
+                        OR (skin_status = 'approved'
+                            AND id IN (SELECT owner_id FROM skin_reports)))
+                 ORDER BY skin_updated_at",
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        let mut queue: Vec<SkinReview> = stmt
+            .query_map([], |row| {
+                Ok(SkinReview {
+                    user_id: row.get(0)?,
+                    username: row.get(1)?,
+                    status: SkinStatus::parse(&row.get::<_, Option<String>>(2)?.unwrap_or_default()),
+                    updated_at: row.get(3)?,
+                    reports: Vec::new(),
+                })
+            })
+            .map_err(|e| format!("DB error: {}", e))?
+            .filter_map(Result::ok)
+            .collect();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT u.username, r.reason, r.created_at FROM skin_reports r
+                 JOIN users u ON u.id = r.reporter_id
+                 WHERE r.owner_id = ?1 ORDER BY r.created_at",
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        for review in &mut queue {
+            review.reports = stmt
+                .query_map(rusqlite::params![review.user_id], |row| {
+                    Ok(SkinReport {
+                        reporter: row.get(0)?,
+                        reason: row.get(1)?,
+                        created_at: row.get(2)?,
+                    })
+                })
+                .map_err(|e| format!("DB error: {}", e))?
+                .filter_map(Result::ok)
+                .collect();
+        }
+        Ok(queue)
+    }
+
+    fn review_skin(
+        &self,
+        user_id: i64,
+        status: SkinStatus,
+        reason: Option<&str>,
+    ) -> Result<(), String> {
+        let conn = self.conn();
+        let updated = conn
+            .execute(
+                "UPDATE users SET skin_status = ?1, skin_reject_reason = ?2
+                 WHERE id = ?3 AND skin_blob IS NOT NULL",
+                rusqlite::params![status.as_str(), reason, user_id],
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        if updated == 0 {
+            return Err("No skin found".into());
+        }
+        conn.execute(
+            "DELETE FROM skin_reports WHERE owner_id = ?1",
+            rusqlite::params![user_id],
+        )
+        .map_err(|e| format!("DB error: {}", e))?;
+        Ok(())
+    }
+
+    fn report_skin(&self, owner_id: i64, reporter_id: i64, reason: &str) -> Result<(), String> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO skin_reports (owner_id, reporter_id, reason) VALUES (?1, ?2, ?3)
+             ON CONFLICT(owner_id, reporter_id) DO UPDATE SET
+                reason = excluded.reason, created_at = datetime('now')",
+            rusqlite::params![owner_id, reporter_id, reason],
+        )
+        .map_err(|e| format!("DB error: {}", e))?;
+        Ok(())
     }
 }
@@ -0,0 +1,95 @@
+use super::Database;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub games_played: i64,
+    pub best_score: i64,
+    pub total_score: i64,
+    pub updated_at: Option<String>,
+}
+
+/// One account's stats, as exported by `export-stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountStats {
+    pub user_id: i64,
+    pub username: String,
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+/// Lifetime per-account stats
+pub trait StatsStore {
+    /// Adds a finished game to the user's lifetime stats. Does nothing if
+    /// the account has been deleted.
+    fn record_game(&self, user_id: i64, score: u64) -> Result<(), String>;
+
+    fn get_stats(&self, user_id: i64) -> Option<Stats>;
+
+    /// Stats for every account that has played, best score first
+    fn all_stats(&self) -> Result<Vec<AccountStats>, String>;
+}
+
+impl StatsStore for Database {
+    fn record_game(&self, user_id: i64, score: u64) -> Result<(), String> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO stats (user_id, games_played, best_score, total_score, updated_at)
+             SELECT ?1, 1, ?2, ?2, datetime('now') WHERE EXISTS (SELECT 1 FROM users WHERE id = ?1)
+             ON CONFLICT(user_id) DO UPDATE SET
+                games_played = games_played + 1,
+                best_score   = MAX(best_score, excluded.best_score),
+                total_score  = total_score + excluded.total_score,
+                updated_at   = excluded.updated_at",
+            rusqlite::params![user_id, score as i64],
+        )
+        .map_err(|e| format!("Failed to save stats: {}", e))?;
+        Ok(())
+    }
+
+    fn get_stats(&self, user_id: i64) -> Option<Stats> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT games_played, best_score, total_score, updated_at FROM stats WHERE user_id = ?1",
+            rusqlite::params![user_id],
+            |row| {
+                Ok(Stats {
+                    games_played: row.get(0)?,
+                    best_score: row.get(1)?,
+                    total_score: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            },
+        )
+        .ok()
+    }
+
+    fn all_stats(&self) -> Result<Vec<AccountStats>, String> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.user_id, u.username, s.games_played, s.best_score, s.total_score,
+                        s.updated_at
+                 FROM stats s JOIN users u ON u.id = s.user_id
+                 ORDER BY s.best_score DESC, s.user_id",
+            )
+            .map_err(|e| format!("Database error: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(AccountStats {
+                    user_id: row.get(0)?,
+                    username: row.get(1)?,
+                    stats: Stats {
+                        games_played: row.get(2)?,
+                        best_score: row.get(3)?,
+                        total_score: row.get(4)?,
+                        updated_at: row.get(5)?,
+                    },
+                })
+            })
+            .map_err(|e| format!("Database error: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Database error: {}", e))?;
+        Ok(rows)
+    }
+}
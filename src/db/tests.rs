@@ -0,0 +1,259 @@
+//! Storage tests, run against both the SQLite and in-memory backends so
+//! the two stay in step.
+
+use super::memory::MemoryStorage;
+use super::{Database, Storage};
+
+enum Backend {
+    Sqlite(Database),
+    Memory(Box<MemoryStorage>),
+}
+
+impl Backend {
+    fn all() -> Vec<Backend> {
+        vec![
+            Backend::Sqlite(Database::new(":memory:")),
+            Backend::Memory(Box::default()),
+        ]
+    }
+
+    fn store(&self) -> &dyn Storage {
+        match self {
+            Backend::Sqlite(db) => db,
+            Backend::Memory(db) => db.as_ref(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Backend::Sqlite(_) => "sqlite",
+            Backend::Memory(_) => "memory",
+        }
+    }
+
+    /// Backdates a session so it has already expired
+    fn expire_session(&self, token: &str) {
+        match self {
+            Backend::Sqlite(db) => {
+                db.conn()
+                    .execute(
+                        "UPDATE sessions SET expires_at = '2000-01-01T00:00:00+00:00' WHERE token = ?1",
+                        rusqlite::params![token],
+                    )
+                    .unwrap();
+            }
+            Backend::Memory(db) => db.expire_session(token),
+        }
+    }
+}
+
+#[test]
+fn register_login_and_logout() {
+    for backend in Backend::all() {
+        let db = backend.store();
+        let name = backend.name();
+
+        let user = db.register("alice", "secret").unwrap();
+        assert_eq!(user.username, "alice", "{}", name);
+        assert!(db.register("Alice", "secret").is_err(), "{}: case variant", name);
+        assert!(db.register("bob", "abc").is_err(), "{}: short password", name);
+
+        assert!(db.login("alice", "wrong").is_err(), "{}", name);
+        assert!(db.login("nobody", "secret").is_err(), "{}", name);
+        let (logged_in, token) = db.login("alice", "secret").unwrap();
+        assert_eq!(logged_in.id, user.id, "{}", name);
+        assert_eq!(db.validate_session(&token).map(|u| u.id), Some(user.id), "{}", name);
+
+        db.logout(&token);
+        assert!(db.validate_session(&token).is_none(), "{}", name);
+    }
+}
+
+#[test]
+fn expired_sessions_are_rejected() {
+    for backend in Backend::all() {
+        let db = backend.store();
+        db.register("alice", "secret").unwrap();
+        let (_, token) = db.login("alice", "secret").unwrap();
+        backend.expire_session(&token);
+        assert!(db.validate_session(&token).is_none(), "{}", backend.name());
+    }
+}
+
+#[test]
+fn change_password_keeps_only_the_current_session() {
+    for backend in Backend::all() {
+        let db = backend.store();
+        let name = backend.name();
+        let user = db.register("alice", "secret").unwrap();
+        let (_, current) = db.login("alice", "secret").unwrap();
+        let (_, other) = db.login("alice", "secret").unwrap();
+
+        assert!(db.change_password(user.id, "wrong", "newpass", &current).is_err(), "{}", name);
+        db.change_password(user.id, "secret", "newpass", &current).unwrap();
+
+        assert!(db.validate_session(&current).is_some(), "{}", name);
+        assert!(db.validate_session(&other).is_none(), "{}", name);
+        assert!(db.login("alice", "secret").is_err(), "{}", name);
+        assert!(db.login("alice", "newpass").is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn export_contains_sessions_skin_and_stats() {
+    for backend in Backend::all() {
+        let db = backend.store();
+        let name = backend.name();
+        let user = db.register("alice", "secret").unwrap();
+        let (_, token) = db.login("alice", "secret").unwrap();
+        db.set_skin(user.id, b"png", "image/png").unwrap();
+        db.record_game(user.id, 120).unwrap();
+        db.record_game(user.id, 80).unwrap();
+
+        let export = db.export_user(user.id, &token).unwrap();
+        assert_eq!(export.username, "alice", "{}", name);
+        assert_eq!(export.sessions.len(), 1, "{}", name);
+        assert!(export.sessions[0].current, "{}", name);
+        assert_eq!(export.skin.map(|s| s.size), Some(3), "{}", name);
+        let stats = export.stats.unwrap();
+        assert_eq!((stats.games_played, stats.best_score, stats.total_score), (2, 120, 200), "{}", name);
+    }
+}
+
+#[test]
+fn delete_account_removes_everything() {
+    for backend in Backend::all() {
+        let db = backend.store();
+        let name = backend.name();
+        let user = db.register("alice", "secret").unwrap();
+        let (_, token) = db.login("alice", "secret").unwrap();
+        db.set_skin(user.id, b"png", "image/png").unwrap();
+        db.record_game(user.id, 50).unwrap();
+
+        db.delete_account(user.id).unwrap();
+        assert!(db.get_user_by_id(user.id).is_none(), "{}", name);
+        assert!(db.validate_session(&token).is_none(), "{}", name);
+        assert!(db.get_skin(user.id).is_none(), "{}", name);
+        assert!(db.get_stats(user.id).is_none(), "{}", name);
+        assert!(db.export_user(user.id, &token).is_none(), "{}", name);
+        assert!(db.delete_account(user.id).is_err(), "{}", name);
+
+        // A game still running when the account went isn't recorded
+        db.record_game(user.id, 30).unwrap();
+        assert!(db.get_stats(user.id).is_none(), "{}", name);
+
+        // The name is free again
+        assert!(db.register("alice", "secret").is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn export_contains_friends_and_pending_requests() {
+    use super::friends::FriendStatus;
+
+    for backend in Backend::all() {
+        let db = backend.store();
+        let name = backend.name();
+        let alice = db.register("alice", "secret").unwrap();
+        let bob = db.register("bob", "secret").unwrap();
+        db.register("carol", "secret").unwrap();
+        let dave = db.register("dave", "secret").unwrap();
+
+        db.request_friend(alice.id, "bob").unwrap();
+        db.accept_friend(bob.id, alice.id).unwrap();
+        db.request_friend(alice.id, "carol").unwrap();
+        db.request_friend(dave.id, "alice").unwrap();
+
+        let export = db.export_user(alice.id, "").unwrap();
+        let friends: Vec<_> = export
+            .friends
+            .iter()
+            .map(|f| (f.username.as_str(), f.status))
+            .collect();
+        assert_eq!(
+            friends,
+            [
+                ("bob", FriendStatus::Friends),
+                ("carol", FriendStatus::Outgoing),
+                ("dave", FriendStatus::Incoming),
+            ],
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn concurrent_registrations_take_a_name_once() {
+    use super::accounts::AccountStore;
+    use std::sync::Arc;
+
+    let dir = std::env::temp_dir().join(format!("agario-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(dir.join("game.db").to_str().unwrap()));
+
+    let names = ["alice", "Alice", "ALICE", "AIice"];
+    let results: Vec<_> = names
+        .into_iter()
+        .map(|name| {
+            let db = db.clone();
+            std::thread::spawn(move || db.register(name, "secret").is_ok())
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|t| t.join().unwrap())
+        .collect();
+    assert_eq!(results.iter().filter(|ok| **ok).count(), 1);
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn bans_match_accounts_addresses_and_networks() {
+    use std::net::IpAddr;
+
+    let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+    for backend in Backend::all() {
+        let db = backend.store();
+        let name = backend.name();
+        let alice = db.register("alice", "secret").unwrap();
+        let bob = db.register("bob", "secret").unwrap();
+
+        let account = db.create_ban(Some(alice.id), None, "griefing", None, "admin").unwrap();
+        let network = db
+            .create_ban(None, Some("10.0.0.0/8".parse().unwrap()), "spam", None, "admin")
+            .unwrap();
+        let expired = Some("2000-01-01T00:00:00+00:00");
+        db.create_ban(None, Some("192.0.2.1".parse().unwrap()), "old", expired, "admin")
+            .unwrap();
+
+        let found = |user: Option<i64>, addr: Option<&str>| {
+            db.find_ban(user, addr.map(ip)).unwrap().map(|b| b.id)
+        };
+        assert_eq!(found(Some(alice.id), None), Some(account.id), "{}", name);
+        assert_eq!(found(Some(bob.id), Some("127.0.0.1")), None, "{}", name);
+        assert_eq!(found(None, Some("10.20.30.40")), Some(network.id), "{}", name);
+        assert_eq!(found(None, Some("::ffff:10.1.2.3")), Some(network.id), "{}", name);
+        assert_eq!(found(None, Some("11.0.0.1")), None, "{}", name);
+        assert_eq!(found(None, Some("192.0.2.1")), None, "{}: expired", name);
+
+        db.lift_ban(account.id).unwrap();
+        assert_eq!(found(Some(alice.id), None), None, "{}", name);
+    }
+}
+
+#[test]
+fn the_longest_matching_ban_wins() {
+    for backend in Backend::all() {
+        let db = backend.store();
+        let user = db.register("alice", "secret").unwrap();
+        db.create_ban(Some(user.id), None, "short", Some("2999-01-01T00:00:00+00:00"), "admin")
+            .unwrap();
+        let permanent = db.create_ban(Some(user.id), None, "forever", None, "admin").unwrap();
+        db.create_ban(Some(user.id), None, "long", Some("3999-01-01T00:00:00+00:00"), "admin")
+            .unwrap();
+        let ban = db.find_ban(Some(user.id), None).unwrap().unwrap();
+        assert_eq!(ban.id, permanent.id, "{}", backend.name());
+    }
+}
@@ -0,0 +1,185 @@
+use super::accounts::{Role, User};
+use super::Database;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Prefix that tells API tokens apart from session tokens
+pub const API_TOKEN_PREFIX: &str = "agt_";
+
+/// What an API token may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Join the game over the WebSocket (as a bot)
+    Play,
+    /// Read the account's profile and stats over REST
+    ReadStats,
+}
+
+impl TokenScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Play => "play",
+            TokenScope::ReadStats => "read_stats",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "play" => Some(TokenScope::Play),
+            "read_stats" => Some(TokenScope::ReadStats),
+            _ => None,
+        }
+    }
+}
+
+pub(super) fn join_scopes(scopes: &[TokenScope]) -> String {
+    scopes.iter().map(TokenScope::as_str).collect::<Vec<_>>().join(",")
+}
+
+pub(super) fn parse_scopes(s: &str) -> Vec<TokenScope> {
+    s.split(',').filter_map(TokenScope::parse).collect()
+}
+
+/// A token as shown to its owner. The secret itself is only returned once,
+/// when the token is created.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// A fresh random token secret
+pub(super) fn new_token_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", API_TOKEN_PREFIX, hex::encode(bytes))
+}
+
+/// Tokens are long and random, so a plain SHA-256 is enough to keep them
+/// useless if the database leaks.
+pub(super) fn hash_token(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// Long-lived API tokens for bots and scripts
+pub trait TokenStore {
+    /// Creates a token and returns it together with its secret.
+    fn create_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scopes: &[TokenScope],
+    ) -> Result<(ApiToken, String), String>;
+
+    fn list_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, String>;
+
+    fn revoke_token(&self, user_id: i64, token_id: i64) -> Result<(), String>;
+
+    /// Resolves a token secret to its owner and scopes, and records the use.
+    fn validate_token(&self, secret: &str) -> Option<(User, Vec<TokenScope>)>;
+}
+
+impl TokenStore for Database {
+    fn create_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scopes: &[TokenScope],
+    ) -> Result<(ApiToken, String), String> {
+        let secret = new_token_secret();
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO api_tokens (user_id, name, token_hash, scopes) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![user_id, name, hash_token(&secret), join_scopes(scopes)],
+        )
+        .map_err(|e| format!("DB error: {}", e))?;
+        let id = conn.last_insert_rowid();
+        let created_at = conn
+            .query_row(
+                "SELECT created_at FROM api_tokens WHERE id = ?1",
+                rusqlite::params![id],
+                |row| row.get(0),
+            )
+            .ok();
+        Ok((
+            ApiToken {
+                id,
+                name: name.to_string(),
+                scopes: scopes.to_vec(),
+                created_at,
+                last_used_at: None,
+            },
+            secret,
+        ))
+    }
+
+    fn list_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, String> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, scopes, created_at, last_used_at FROM api_tokens
+                 WHERE user_id = ?1 ORDER BY id",
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        let tokens = stmt
+            .query_map(rusqlite::params![user_id], |row| {
+                Ok(ApiToken {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    scopes: parse_scopes(&row.get::<_, String>(2)?),
+                    created_at: row.get(3)?,
+                    last_used_at: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("DB error: {}", e))?
+            .filter_map(Result::ok)
+            .collect();
+        Ok(tokens)
+    }
+
+    fn revoke_token(&self, user_id: i64, token_id: i64) -> Result<(), String> {
+        let conn = self.conn();
+        let deleted = conn
+            .execute(
+                "DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2",
+                rusqlite::params![token_id, user_id],
+            )
+            .map_err(|e| format!("DB error: {}", e))?;
+        if deleted == 0 {
+            return Err("Token not found".into());
+        }
+        Ok(())
+    }
+
+    fn validate_token(&self, secret: &str) -> Option<(User, Vec<TokenScope>)> {
+        let conn = self.conn();
+        let hash = hash_token(secret);
+        let (user, scopes) = conn
+            .query_row(
+                "SELECT u.id, u.username, u.role, t.scopes FROM api_tokens t
+                 JOIN users u ON u.id = t.user_id WHERE t.token_hash = ?1",
+                rusqlite::params![hash],
+                |row| {
+                    Ok((
+                        User {
+                            id: row.get(0)?,
+                            username: row.get(1)?,
+                            role: Role::parse(&row.get::<_, String>(2)?),
+                        },
+                        parse_scopes(&row.get::<_, String>(3)?),
+                    ))
+                },
+            )
+            .ok()?;
+        let _ = conn.execute(
+            "UPDATE api_tokens SET last_used_at = datetime('now') WHERE token_hash = ?1",
+            rusqlite::params![hash],
+        );
+        Some((user, scopes))
+    }
+}
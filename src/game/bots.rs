@@ -0,0 +1,281 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use tokio::time::{interval, Duration};
+
+use crate::config::*;
+use crate::db::SharedDb;
+use crate::game::engine::{build_state_for_player, SharedWorld};
+use crate::game::physics::{distance, normalize};
+use crate::game::world::World;
+use crate::names::name_key;
+use crate::protocol::messages::{CellState, FoodState, ServerMessage, VirusState};
+
+const BOT_NAMES: &[&str] = &[
+    "Blobby", "Nom", "Pellet", "Gloop", "Mitosis", "Amoeba", "Chonk", "Orbit", "Puddle",
+    "Sprout", "Wobble", "Drift", "Bubbles", "Zygote", "Plankton", "Gulp", "Dot", "Morsel",
+];
+
+/// What a bot does until it next thinks
+#[derive(Debug, Clone, Copy)]
+pub struct Decision {
+    pub target: (f64, f64),
+    pub split: bool,
+}
+
+/// A visible cell, with its mass recovered from its radius
+#[derive(Clone, Copy)]
+struct Seen {
+    x: f64,
+    y: f64,
+    radius: f64,
+    mass: f64,
+}
+
+impl From<&CellState> for Seen {
+    fn from(c: &CellState) -> Self {
+        Seen {
+            x: c.x,
+            y: c.y,
+            radius: c.radius,
+            mass: radius_to_mass(c.radius),
+        }
+    }
+}
+
+/// Picks a bot's next move from the same view a human client gets. In
+/// order of priority: run from cells that could eat it, chase (and
+/// split-kill) smaller players, eat food, wander. Large bots steer around
+/// viruses. `current` is where the bot is heading now.
+pub fn decide(me: u64, view: &ServerMessage, current: (f64, f64)) -> Option<Decision> {
+    let ServerMessage::State {
+        players,
+        food,
+        viruses,
+        ..
+    } = view
+    else {
+        return None;
+    };
+    let own: Vec<Seen> = players
+        .iter()
+        .find(|p| p.id == me)?
+        .cells
+        .iter()
+        .map(Seen::from)
+        .collect();
+    let others: Vec<Seen> = players
+        .iter()
+        .filter(|p| p.id != me)
+        .flat_map(|p| p.cells.iter().map(Seen::from))
+        .collect();
+    let total: f64 = own.iter().map(|c| c.mass).sum();
+    let center = (
+        own.iter().map(|c| c.x * c.mass).sum::<f64>() / total,
+        own.iter().map(|c| c.y * c.mass).sum::<f64>() / total,
+    );
+    let biggest = own.iter().copied().max_by(|a, b| a.mass.total_cmp(&b.mass))?;
+
+    // Flee: push away from every cell that could eat one of ours, harder
+    // the closer it is
+    let mut away = (0.0, 0.0);
+    for threat in &others {
+        for cell in own.iter().filter(|c| threat.mass > c.mass * EAT_MASS_RATIO) {
+            let gap = distance(cell.x, cell.y, threat.x, threat.y) - threat.radius - cell.radius;
+            if gap < BOT_FLEE_DISTANCE {
+                let (nx, ny) = normalize(cell.x - threat.x, cell.y - threat.y);
+                let weight = 1.0 / gap.max(10.0);
+                away.0 += nx * weight;
+                away.1 += ny * weight;
+            }
+        }
+    }
+    if away != (0.0, 0.0) {
+        let (nx, ny) = normalize(away.0, away.1);
+        let target = (center.0 + nx * 500.0, center.1 + ny * 500.0);
+        return Some(Decision {
+            target: avoid_viruses(center, target, biggest, viruses),
+            split: false,
+        });
+    }
+
+    // Chase: the most mass for the least distance
+    let prey = others
+        .iter()
+        .filter(|o| o.mass * EAT_MASS_RATIO < biggest.mass)
+        .max_by(|a, b| {
+            let score = |o: &Seen| o.mass / distance(biggest.x, biggest.y, o.x, o.y).max(1.0);
+            score(a).total_cmp(&score(b))
+        });
+    if let Some(prey) = prey {
+        let half = biggest.mass / 2.0;
+        let reach = distance(biggest.x, biggest.y, prey.x, prey.y) - biggest.radius;
+        // Split only when half of us still eats it, it's in range, and
+        // nothing else nearby could eat the halves
+        let split = own.len() < MAX_CELLS_PER_PLAYER
+            && biggest.mass >= SPLIT_MIN_MASS
+            && prey.mass * EAT_MASS_RATIO < half
+            && reach < BOT_SPLIT_RANGE
+            && !others.iter().any(|o| {
+                o.mass > half * EAT_MASS_RATIO
+                    && distance(center.0, center.1, o.x, o.y) < BOT_SPLIT_SAFE_DISTANCE
+            });
+        return Some(Decision {
+            target: (prey.x, prey.y),
+            split,
+        });
+    }
+
+    // Eat the nearest food
+    let nearest = food.iter().min_by(|a, b| {
+        let d = |f: &&FoodState| distance(center.0, center.1, f.x, f.y);
+        d(a).total_cmp(&d(b))
+    });
+    let target = match nearest {
+        Some(f) => (f.x, f.y),
+        // Nothing in sight: keep going, or pick somewhere new
+        None if distance(center.0, center.1, current.0, current.1) > 50.0 => current,
+        None => {
+            let mut rng = rand::thread_rng();
+            (rng.gen_range(0.0..WORLD_SIZE), rng.gen_range(0.0..WORLD_SIZE))
+        }
+    };
+    Some(Decision {
+        target: avoid_viruses(center, target, biggest, viruses),
+        split: false,
+    })
+}
+
+/// Bends the path from `center` to `target` around nearby viruses when
+/// the bot is big enough to be popped by one.
+fn avoid_viruses(
+    center: (f64, f64),
+    target: (f64, f64),
+    biggest: Seen,
+    viruses: &[VirusState],
+) -> (f64, f64) {
+    if biggest.mass < VIRUS_SPLIT_MIN_MASS {
+        return target;
+    }
+    let (mut dx, mut dy) = normalize(target.0 - center.0, target.1 - center.1);
+    for v in viruses {
+        let gap = distance(center.0, center.1, v.x, v.y) - v.radius - biggest.radius;
+        if gap < BOT_VIRUS_AVOID_DISTANCE {
+            let (nx, ny) = normalize(center.0 - v.x, center.1 - v.y);
+            let weight = 2.0 * (1.0 - gap.max(0.0) / BOT_VIRUS_AVOID_DISTANCE);
+            dx += nx * weight;
+            dy += ny * weight;
+        }
+    }
+    let (nx, ny) = normalize(dx, dy);
+    (center.0 + nx * 300.0, center.1 + ny * 300.0)
+}
+
+/// Clears out dead bots and removes surplus ones, smallest first, so that
+/// players plus bots don't exceed `min_population`. Returns how many bots
+/// are missing. The population is left alone during a battle royale round.
+fn balance(world: &mut World, min_population: usize) -> usize {
+    let dead: Vec<u64> = world
+        .players
+        .values()
+        .filter(|p| p.ai && !p.alive)
+        .map(|p| p.id)
+        .collect();
+    for id in dead {
+        world.remove_player(id);
+    }
+    if world.can_join().is_err() {
+        return 0;
+    }
+
+    let humans = world.players.values().filter(|p| !p.ai).count();
+    let mut bots: Vec<(u64, f64)> = world
+        .players
+        .values()
+        .filter(|p| p.ai)
+        .map(|p| (p.id, p.total_mass()))
+        .collect();
+    let wanted = min_population.saturating_sub(humans);
+
+    if bots.len() > wanted {
+        bots.sort_by(|a, b| a.1.total_cmp(&b.1));
+        for (id, _) in &bots[..bots.len() - wanted] {
+            world.remove_player(*id);
+        }
+    }
+    wanted.saturating_sub(bots.len())
+}
+
+/// Candidate names for `count` new bots: unused names from the list in
+/// random order, then numbered fallbacks. Some may turn out to be
+/// registered, so there are more candidates than bots.
+fn candidate_names(world: &World, count: usize) -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    let taken: Vec<String> = world.players.values().map(|p| name_key(&p.name)).collect();
+    let mut names: Vec<String> = BOT_NAMES
+        .iter()
+        .filter(|n| !taken.contains(&name_key(n)))
+        .map(|n| n.to_string())
+        .collect();
+    names.shuffle(&mut rng);
+    names.extend((0..count).map(|_| format!("Bot {}", rng.gen_range(100..1000))));
+    names
+}
+
+/// Runs the server's bots: keeps the population topped up and lets each
+/// bot decide its next move every `BOT_THINK_INTERVAL_MS`.
+pub async fn run(world: SharedWorld, db: SharedDb) {
+    let mut tick = interval(Duration::from_millis(BOT_THINK_INTERVAL_MS));
+    loop {
+        tick.tick().await;
+        let min_population = settings().bots.min_population;
+        let missing = balance(&mut *world.write().await, min_population);
+        if missing > 0 {
+            let candidates = candidate_names(&*world.read().await, missing);
+            // Bots mustn't pass for registered players, just like guests
+            let names: Vec<String> = db
+                .run(move |db| {
+                    candidates
+                        .into_iter()
+                        .filter(|n| db.find_user_by_name_key(&name_key(n)).is_none())
+                        .take(missing)
+                        .collect()
+                })
+                .await;
+            let mut world = world.write().await;
+            if world.can_join().is_ok() {
+                for name in names {
+                    world.add_bot(name);
+                }
+            }
+        }
+
+        // Building every bot's view is the expensive part, so it happens
+        // under the read lock; only the results need the write lock
+        let decisions: Vec<(u64, Decision)> = {
+            let world = world.read().await;
+            world
+                .players
+                .values()
+                .filter(|p| p.ai && p.alive)
+                .filter_map(|p| {
+                    let view = build_state_for_player(&world, p.id)?;
+                    decide(p.id, &view, (p.target_x, p.target_y)).map(|d| (p.id, d))
+                })
+                .collect()
+        };
+        if decisions.is_empty() {
+            continue;
+        }
+        let mut world = world.write().await;
+        for (id, decision) in decisions {
+            // The bot may have died or left since its view was built
+            let Some(bot) = world.players.get_mut(&id).filter(|p| p.alive) else {
+                continue;
+            };
+            (bot.target_x, bot.target_y) = decision.target;
+            if decision.split {
+                world.split_player(id);
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock, Mutex};
 use tokio::sync::RwLock;
-use tokio::time::{interval, Duration};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use tokio::time::{interval, Duration, Instant, MissedTickBehavior};
 use crate::config::*;
-use crate::game::world::World;
+use crate::game::world::{TickTimings, World};
+use crate::metrics::{inc, METRICS};
 use crate::protocol::messages::*;
 
 pub type SharedWorld = Arc<RwLock<World>>;
@@ -11,14 +13,223 @@ pub fn create_world() -> SharedWorld {
     Arc::new(RwLock::new(World::new()))
 }
 
+/// Liveness of the game loop, read by the health endpoints
+pub struct LoopStatus {
+    state: Mutex<LoopState>,
+}
+
+struct LoopState {
+    started: Instant,
+    last_tick: Option<Instant>,
+    /// Ticks per second over the last full second
+    tps: f64,
+    crashed: bool,
+}
+
+pub static LOOP_STATUS: LazyLock<LoopStatus> = LazyLock::new(|| LoopStatus {
+    state: Mutex::new(LoopState {
+        started: Instant::now(),
+        last_tick: None,
+        tps: 0.0,
+        crashed: false,
+    }),
+});
+
+impl LoopStatus {
+    fn ticked(&self, at: Instant) {
+        self.state.lock().unwrap().last_tick = Some(at);
+    }
+
+    fn set_tps(&self, tps: f64) {
+        self.state.lock().unwrap().tps = tps;
+    }
+
+    /// Called when the game loop task ends, which only happens on a panic
+    pub fn mark_crashed(&self) {
+        self.state.lock().unwrap().crashed = true;
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.state.lock().unwrap().started.elapsed()
+    }
+
+    pub fn tps(&self) -> f64 {
+        self.state.lock().unwrap().tps
+    }
+
+    /// Why the loop isn't healthy, if it isn't
+    pub fn problem(&self, max_tick_age: Duration) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        if state.crashed {
+            return Some("game loop crashed".into());
+        }
+        match state.last_tick {
+            None => Some("game loop hasn't ticked yet".into()),
+            Some(at) if at.elapsed() > max_tick_age => Some(format!(
+                "game loop last ticked {}ms ago",
+                at.elapsed().as_millis()
+            )),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Operator controls for the game loop
+pub struct LoopControl {
+    paused: AtomicBool,
+    /// Ticks to run while paused
+    steps: AtomicU32,
+}
+
+pub static LOOP_CONTROL: LoopControl = LoopControl {
+    paused: AtomicBool::new(false),
+    steps: AtomicU32::new(0),
+};
+
+impl LoopControl {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.steps.store(0, Ordering::Relaxed);
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Queues `n` ticks to run while paused
+    pub fn step(&self, n: u32) {
+        self.steps.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+/// Per-phase tick timings collected between two log lines
+#[derive(Default)]
+struct TickReport {
+    ticks: u32,
+    slowest: Duration,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl TickReport {
+    fn add(&mut self, timings: &TickTimings) {
+        self.ticks += 1;
+        self.slowest = self.slowest.max(timings.total());
+        if self.phases.is_empty() {
+            self.phases = timings.phases.clone();
+        } else {
+            for ((_, sum), (_, d)) in self.phases.iter_mut().zip(&timings.phases) {
+                *sum += *d;
+            }
+        }
+    }
+
+    fn log(&mut self) {
+        if self.ticks == 0 {
+            return;
+        }
+        let average = TickTimings {
+            phases: self
+                .phases
+                .iter()
+                .map(|(phase, sum)| (*phase, *sum / self.ticks))
+                .collect(),
+        };
+        tracing::info!(
+            "{} ticks, avg {:.2}ms, max {:.2}ms ({})",
+            self.ticks,
+            average.total().as_secs_f64() * 1000.0,
+            self.slowest.as_secs_f64() * 1000.0,
+            average
+        );
+        *self = TickReport::default();
+    }
+}
+
+/// Runs the simulation at a fixed `TICK_RATE`. Real elapsed time goes into
+/// an accumulator that is drained in fixed steps, so a late wake-up runs
+/// the missed ticks; after a long stall at most `MAX_CATCHUP_TICKS` are
+/// run and the rest are dropped.
 pub async fn game_loop(world: SharedWorld) {
-    let mut tick_interval = interval(Duration::from_millis(TICK_DURATION_MS));
-    let dt = 1.0 / TICK_RATE as f64;
+    let step = Duration::from_secs_f64(1.0 / TICK_RATE as f64);
+    let dt = step.as_secs_f64();
+    let mut tick_interval = interval(step);
+    // Catch-up is the accumulator's job, not the timer's
+    tick_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut last = Instant::now();
+    let mut accumulator = Duration::ZERO;
+    let mut report = TickReport::default();
+    let mut next_report = Instant::now() + Duration::from_secs(TICK_REPORT_INTERVAL_SECS);
+    let mut tps_window = (Instant::now(), 0u32);
 
     loop {
         tick_interval.tick().await;
+        let now = Instant::now();
+        accumulator += now - last;
+        last = now;
+
+        // While paused only queued steps run, and no backlog builds up
+        let budget = if LOOP_CONTROL.is_paused() {
+            accumulator = Duration::ZERO;
+            LOOP_CONTROL.steps.swap(0, Ordering::Relaxed)
+        } else {
+            MAX_CATCHUP_TICKS
+        };
+
         let mut w = world.write().await;
-        w.tick(dt);
+        let mut steps = 0;
+        while steps < budget && (accumulator >= step || LOOP_CONTROL.is_paused()) {
+            let start = Instant::now();
+            let timings = w.tick(dt);
+            let elapsed = start.elapsed();
+
+            METRICS.tick_duration.observe(elapsed.as_secs_f64());
+            METRICS.record_tick_phases(timings.phases.iter());
+            if elapsed > step {
+                inc(&METRICS.tick_overruns);
+                tracing::warn!(
+                    "Tick took {:.2}ms, over its {:.2}ms budget ({})",
+                    elapsed.as_secs_f64() * 1000.0,
+                    step.as_secs_f64() * 1000.0,
+                    timings
+                );
+            }
+            report.add(&timings);
+
+            accumulator = accumulator.saturating_sub(step);
+            steps += 1;
+        }
+        drop(w);
+        // A paused loop is still alive
+        if steps > 0 || LOOP_CONTROL.is_paused() {
+            LOOP_STATUS.ticked(Instant::now());
+        }
+
+        tps_window.1 += steps;
+        let window = tps_window.0.elapsed();
+        if window >= Duration::from_secs(1) {
+            LOOP_STATUS.set_tps(tps_window.1 as f64 / window.as_secs_f64());
+            tps_window = (Instant::now(), 0);
+        }
+
+        if accumulator >= step {
+            let behind = (accumulator.as_nanos() / step.as_nanos()) as u32;
+            METRICS.ticks_skipped.fetch_add(behind as u64, Ordering::Relaxed);
+            tracing::warn!(
+                "Game loop fell {} ticks behind; skipping them",
+                behind
+            );
+            accumulator -= step * behind;
+        }
+
+        if now >= next_report {
+            report.log();
+            next_report = now + Duration::from_secs(TICK_REPORT_INTERVAL_SECS);
+        }
     }
 }
 
@@ -53,7 +264,9 @@ pub fn build_state_for_player(world: &World, player_id: u64) -> Option<ServerMes
         .map(|p| PlayerState {
             id: p.id,
             name: p.name.clone(),
-            skin: p.skin_url(),
+            verified: p.verified,
+            bot: p.bot,
+            skin: p.skin_url(player_id),
             cells: p
                 .cells
                 .iter()
@@ -63,6 +276,14 @@ pub fn build_state_for_player(world: &World, player_id: u64) -> Option<ServerMes
                     radius: c.radius(),
                 })
                 .collect(),
+            effects: p
+                .effects
+                .iter()
+                .map(|(kind, seconds)| EffectState {
+                    kind: kind.as_str(),
+                    seconds: *seconds,
+                })
+                .collect(),
         })
         .collect();
 
@@ -95,6 +316,36 @@ pub fn build_state_for_player(world: &World, player_id: u64) -> Option<ServerMes
         })
         .collect();
 
+    // Collect visible mothercells
+    let mothercells: Vec<MothercellState> = world
+        .mothercells
+        .iter()
+        .filter(|m| {
+            let r = m.radius();
+            m.x + r > view_left
+                && m.x - r < view_right
+                && m.y + r > view_top
+                && m.y - r < view_bottom
+        })
+        .map(|m| MothercellState {
+            x: m.x,
+            y: m.y,
+            radius: m.radius(),
+        })
+        .collect();
+
+    // Collect visible power-ups
+    let power_ups: Vec<PowerUpState> = world
+        .power_ups
+        .iter()
+        .filter(|p| p.x > view_left && p.x < view_right && p.y > view_top && p.y < view_bottom)
+        .map(|p| PowerUpState {
+            x: p.x,
+            y: p.y,
+            kind: p.kind.as_str(),
+        })
+        .collect();
+
     let leaderboard: Vec<LeaderboardEntry> = world
         .get_leaderboard()
         .into_iter()
@@ -105,6 +356,9 @@ pub fn build_state_for_player(world: &World, player_id: u64) -> Option<ServerMes
         players,
         food,
         viruses,
+        power_ups,
+        mothercells,
         leaderboard,
+        round: world.round_state(),
     })
 }
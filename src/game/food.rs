@@ -1,39 +1,47 @@
 use crate::config::*;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Food {
     pub x: f64,
     pub y: f64,
     pub color: String,
 }
 
+const FOOD_COLORS: [&str; 10] = [
+    "#FF6384", "#36A2EB", "#FFCE56", "#4BC0C0", "#9966FF",
+    "#FF9F40", "#E7E9ED", "#7CB342", "#F06292", "#4DD0E1",
+];
+
 impl Food {
     pub fn random() -> Self {
         let mut rng = rand::thread_rng();
-        let colors = [
-            "#FF6384", "#36A2EB", "#FFCE56", "#4BC0C0", "#9966FF",
-            "#FF9F40", "#E7E9ED", "#7CB342", "#F06292", "#4DD0E1",
-        ];
+        Food::at(rng.gen_range(0.0..WORLD_SIZE), rng.gen_range(0.0..WORLD_SIZE))
+    }
+
+    pub fn at(x: f64, y: f64) -> Self {
+        let mut rng = rand::thread_rng();
         Food {
-            x: rng.gen_range(0.0..WORLD_SIZE),
-            y: rng.gen_range(0.0..WORLD_SIZE),
-            color: colors[rng.gen_range(0..colors.len())].to_string(),
+            x: x.clamp(0.0, WORLD_SIZE),
+            y: y.clamp(0.0, WORLD_SIZE),
+            color: FOOD_COLORS[rng.gen_range(0..FOOD_COLORS.len())].to_string(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EjectedMass {
     pub x: f64,
     pub y: f64,
     pub mass: f64,
     pub vx: f64,
     pub vy: f64,
+    #[allow(dead_code)]
     pub color: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Virus {
     pub x: f64,
     pub y: f64,
@@ -48,3 +56,105 @@ impl Virus {
         }
     }
 }
+
+/// Grows over time and sheds its growth as bursts of food around itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mothercell {
+    pub x: f64,
+    pub y: f64,
+    pub mass: f64,
+    /// Seconds until the next burst
+    pub burst_timer: f64,
+}
+
+impl Mothercell {
+    pub fn random(rule: &MothercellRule) -> Self {
+        let mut rng = rand::thread_rng();
+        let r = mass_to_radius(rule.max_mass).min(WORLD_SIZE / 4.0);
+        Mothercell {
+            x: rng.gen_range(r..WORLD_SIZE - r),
+            y: rng.gen_range(r..WORLD_SIZE - r),
+            mass: rule.start_mass,
+            burst_timer: rule.burst_secs,
+        }
+    }
+
+    pub fn radius(&self) -> f64 {
+        mass_to_radius(self.mass)
+    }
+
+    /// Spends up to `rule.burst_food` of the mass grown past `start_mass`
+    /// on food scattered around the cell
+    pub fn burst(&mut self, rule: &MothercellRule) -> Vec<Food> {
+        let grown = ((self.mass - rule.start_mass) / FOOD_MASS).max(0.0) as usize;
+        let count = grown.min(rule.burst_food);
+        self.mass -= count as f64 * FOOD_MASS;
+        let mut rng = rand::thread_rng();
+        let r = self.radius();
+        (0..count)
+            .map(|_| {
+                let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+                let dist = r + rng.gen_range(FOOD_RADIUS..MOTHERCELL_BURST_SPREAD);
+                Food::at(self.x + angle.cos() * dist, self.y + angle.sin() * dist)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerUpKind {
+    /// Moves faster
+    Speed,
+    /// Can't be eaten by other players
+    Shield,
+    /// Pulls nearby food in
+    Magnet,
+    /// Split cells merge back sooner
+    Merge,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::Speed,
+        PowerUpKind::Shield,
+        PowerUpKind::Magnet,
+        PowerUpKind::Merge,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowerUpKind::Speed => "speed",
+            PowerUpKind::Shield => "shield",
+            PowerUpKind::Magnet => "magnet",
+            PowerUpKind::Merge => "merge",
+        }
+    }
+
+    pub fn rule<'a>(&self, settings: &'a PowerUpSettings) -> &'a PowerUpRule {
+        match self {
+            PowerUpKind::Speed => &settings.speed,
+            PowerUpKind::Shield => &settings.shield,
+            PowerUpKind::Magnet => &settings.magnet,
+            PowerUpKind::Merge => &settings.merge,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerUp {
+    pub x: f64,
+    pub y: f64,
+    pub kind: PowerUpKind,
+}
+
+impl PowerUp {
+    pub fn random(kind: PowerUpKind) -> Self {
+        let mut rng = rand::thread_rng();
+        PowerUp {
+            x: rng.gen_range(POWER_UP_RADIUS..WORLD_SIZE - POWER_UP_RADIUS),
+            y: rng.gen_range(POWER_UP_RADIUS..WORLD_SIZE - POWER_UP_RADIUS),
+            kind,
+        }
+    }
+}
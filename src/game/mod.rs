@@ -1,5 +1,8 @@
+pub mod bots;
 pub mod engine;
 pub mod world;
 pub mod player;
 pub mod food;
 pub mod physics;
+pub mod round;
+pub mod snapshot;
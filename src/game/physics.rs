@@ -4,6 +4,7 @@ pub fn distance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
     ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt()
 }
 
+#[allow(dead_code)]
 pub fn circles_overlap(x1: f64, y1: f64, r1: f64, x2: f64, y2: f64, r2: f64) -> bool {
     distance(x1, y1, x2, y2) < r1 + r2
 }
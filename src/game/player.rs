@@ -1,6 +1,13 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
 use crate::config::*;
+use crate::game::food::PowerUpKind;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cell {
     pub x: f64,
     pub y: f64,
@@ -27,17 +34,50 @@ impl Cell {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Who gets to see a player's uploaded skin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkinVisibility {
+    Hidden,
+    /// Awaiting moderation; only the owner sees it
+    OwnerOnly,
+    Public,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: u64,
     pub name: String,
     pub user_id: Option<i64>, // database user id if logged in
+    pub skin: SkinVisibility,
+    pub verified: bool, // playing under their own registered username
+    pub bot: bool,      // joined with an API token, or a server-side bot
+    /// Driven by the server's bot AI. Never saved: bots aren't snapshotted.
+    #[serde(skip)]
+    pub ai: bool,
     pub cells: Vec<Cell>,
     pub target_x: f64,
     pub target_y: f64,
     pub color: String,
     pub alive: bool,
     pub score: u64,
+    /// Secret the owner presents to take the player back after a restart
+    pub resume_token: String,
+    /// Set on players restored from a snapshot until their owner comes
+    /// back; they're removed once it passes
+    #[serde(skip)]
+    pub reclaim_until: Option<Instant>,
+    /// Active power-up effects and the seconds each has left
+    #[serde(default)]
+    pub effects: HashMap<PowerUpKind, f64>,
+    /// When the player last asked to split, for rate limiting
+    #[serde(skip)]
+    pub last_split: Option<Instant>,
+    /// Holding the eject key
+    #[serde(skip)]
+    pub ejecting: bool,
+    /// Seconds of game time until the player may eject again
+    #[serde(skip)]
+    pub eject_cooldown: f64,
 }
 
 impl Player {
@@ -47,15 +87,34 @@ impl Player {
             id,
             name,
             user_id,
+            skin: SkinVisibility::Hidden,
+            verified: false,
+            bot: false,
+            ai: false,
             cells: vec![Cell::new(x, y, STARTING_MASS)],
             target_x: x,
             target_y: y,
             color,
             alive: true,
             score: 0,
+            resume_token: Uuid::new_v4().simple().to_string(),
+            reclaim_until: None,
+            effects: HashMap::new(),
+            last_split: None,
+            ejecting: false,
+            eject_cooldown: 0.0,
         }
     }
 
+    /// Restored from a snapshot and not yet reclaimed
+    pub fn is_disconnected(&self) -> bool {
+        self.reclaim_until.is_some()
+    }
+
+    pub fn has_effect(&self, kind: PowerUpKind) -> bool {
+        self.effects.contains_key(&kind)
+    }
+
     pub fn total_mass(&self) -> f64 {
         self.cells.iter().map(|c| c.mass).sum()
     }
@@ -85,7 +144,16 @@ impl Player {
         }
     }
 
-    pub fn skin_url(&self) -> Option<String> {
+    /// Skin URL as seen by `viewer_id`
+    pub fn skin_url(&self, viewer_id: u64) -> Option<String> {
+        let visible = match self.skin {
+            SkinVisibility::Hidden => false,
+            SkinVisibility::OwnerOnly => viewer_id == self.id,
+            SkinVisibility::Public => true,
+        };
+        if !visible {
+            return None;
+        }
         self.user_id.map(|uid| format!("/api/skin/{}", uid))
     }
 
@@ -0,0 +1,361 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::config::*;
+use crate::db::SharedDb;
+use crate::game::engine::SharedWorld;
+use crate::game::player::Cell;
+use crate::game::world::World;
+use crate::protocol::messages::{RoundState, ZoneState};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+}
+
+impl Circle {
+    /// Covers the whole world
+    fn full() -> Self {
+        Circle {
+            x: WORLD_SIZE / 2.0,
+            y: WORLD_SIZE / 2.0,
+            radius: WORLD_SIZE * std::f64::consts::FRAC_1_SQRT_2 + 1.0,
+        }
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        (x - self.x).powi(2) + (y - self.y).powi(2) <= self.radius.powi(2)
+    }
+
+    fn lerp(&self, to: &Circle, t: f64) -> Circle {
+        Circle {
+            x: self.x + (to.x - self.x) * t,
+            y: self.y + (to.y - self.y) * t,
+            radius: self.radius + (to.radius - self.radius) * t,
+        }
+    }
+
+    /// A random circle of `radius` that fits inside this one and the world
+    fn random_inside(&self, radius: f64) -> Circle {
+        let radius = radius.min(self.radius).max(0.0);
+        let mut rng = rand::thread_rng();
+        let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+        let offset = rng.gen_range(0.0..=(self.radius - radius));
+        let margin = radius.min(WORLD_SIZE / 2.0);
+        let bound = |v: f64| v.clamp(margin, WORLD_SIZE - margin);
+        Circle {
+            x: bound(self.x + angle.cos() * offset),
+            y: bound(self.y + angle.sin() * offset),
+            radius,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for enough players
+    Lobby,
+    /// Enough players are in; counting down to the start
+    Starting,
+    Running,
+    /// Showing the winner before the lobby opens again
+    Finished,
+}
+
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Lobby => "lobby",
+            Phase::Starting => "starting",
+            Phase::Running => "running",
+            Phase::Finished => "finished",
+        }
+    }
+}
+
+/// Something that happened to the round, for `record_results`
+#[derive(Debug)]
+pub enum RoundEvent {
+    Started {
+        players: usize,
+    },
+    Finished {
+        /// SQLite `datetime('now')` format
+        started_at: String,
+        players: usize,
+        /// Account and name of the last player alive
+        winner: Option<(Option<i64>, String)>,
+    },
+}
+
+/// Battle royale round state, kept in `World::round`
+#[derive(Debug)]
+pub struct Round {
+    pub phase: Phase,
+    /// Seconds left of the countdown, the results screen, or the zone's
+    /// current wait or shrink
+    pub timer: f64,
+    pub zone: Circle,
+    /// Where the zone is heading in the current stage
+    pub next_zone: Option<Circle>,
+    pub winner: Option<String>,
+    /// Rules for this round, fixed when it starts
+    pub rules: BattleRoyaleSettings,
+    from_zone: Circle,
+    stage: usize,
+    shrinking: bool,
+    started_at: String,
+    entrants: usize,
+    events: Vec<RoundEvent>,
+}
+
+impl Round {
+    pub fn new() -> Self {
+        Round {
+            phase: Phase::Lobby,
+            timer: 0.0,
+            zone: Circle::full(),
+            next_zone: None,
+            winner: None,
+            rules: settings().battle_royale.clone(),
+            from_zone: Circle::full(),
+            stage: 0,
+            shrinking: false,
+            started_at: String::new(),
+            entrants: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn min_players(&self) -> usize {
+        self.rules.min_players.max(2)
+    }
+
+    /// Heads for the next stage's circle, or stops once the schedule ends
+    fn begin_stage(&mut self) {
+        self.from_zone = self.zone;
+        self.shrinking = false;
+        match self.rules.zone_stages.get(self.stage) {
+            Some(stage) => {
+                self.next_zone = Some(self.zone.random_inside(stage.radius));
+                self.timer = stage.wait_secs;
+            }
+            None => {
+                self.next_zone = None;
+                self.timer = 0.0;
+            }
+        }
+    }
+
+    /// Moves the zone along its schedule by `dt` seconds
+    fn advance_zone(&mut self, dt: f64) {
+        let Some(next) = self.next_zone else { return };
+        let stage = &self.rules.zone_stages[self.stage];
+        self.timer -= dt;
+        if !self.shrinking {
+            if self.timer <= 0.0 {
+                self.shrinking = true;
+                self.timer += stage.shrink_secs;
+            } else {
+                return;
+            }
+        }
+        if self.timer <= 0.0 {
+            self.zone = next;
+            self.stage += 1;
+            self.begin_stage();
+            return;
+        }
+        let t = 1.0 - self.timer / stage.shrink_secs;
+        self.zone = self.from_zone.lerp(&next, t.clamp(0.0, 1.0));
+    }
+}
+
+impl World {
+    /// Players can only join between rounds; there are no respawns
+    pub fn can_join(&self) -> Result<(), String> {
+        match &self.round {
+            Some(round) if round.phase == Phase::Running => {
+                Err("A round is in progress; join the next one".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Players only eat each other while a round is running
+    pub fn pvp_enabled(&self) -> bool {
+        self.round.as_ref().is_none_or(|r| r.phase == Phase::Running)
+    }
+
+    /// The round as sent to clients
+    pub fn round_state(&self) -> Option<RoundState> {
+        let round = self.round.as_ref()?;
+        let zone = |c: &Circle| ZoneState {
+            x: c.x,
+            y: c.y,
+            radius: c.radius,
+        };
+        let timed = match round.phase {
+            Phase::Lobby => false,
+            Phase::Running => round.next_zone.is_some(),
+            Phase::Starting | Phase::Finished => true,
+        };
+        // While waiting, only people count towards `min_players`
+        let waiting = matches!(round.phase, Phase::Lobby | Phase::Starting);
+        Some(RoundState {
+            phase: round.phase.as_str(),
+            alive: self
+                .players
+                .values()
+                .filter(|p| p.alive && !(waiting && p.ai))
+                .count(),
+            min_players: round.min_players(),
+            seconds: timed.then(|| round.timer.max(0.0).ceil() as u64),
+            zone: zone(&round.zone),
+            next_zone: round.next_zone.as_ref().map(zone),
+            winner: round.winner.clone(),
+        })
+    }
+
+    pub fn take_round_events(&mut self) -> Vec<RoundEvent> {
+        self.round
+            .as_mut()
+            .map(|r| std::mem::take(&mut r.events))
+            .unwrap_or_default()
+    }
+
+    /// Steps the battle royale round: lobby countdown, the zone and its
+    /// damage, and the end of the round
+    pub(crate) fn update_round(&mut self, dt: f64) {
+        let Some(mut round) = self.round.take() else {
+            return;
+        };
+        // Bots fill out a round but don't start one
+        let humans = self.players.values().filter(|p| p.alive && !p.ai).count();
+        match round.phase {
+            Phase::Lobby => {
+                if humans >= round.min_players() {
+                    round.phase = Phase::Starting;
+                    round.timer = round.rules.countdown_secs as f64;
+                }
+            }
+            Phase::Starting => {
+                round.timer -= dt;
+                if humans < round.min_players() {
+                    round.phase = Phase::Lobby;
+                } else if round.timer <= 0.0 {
+                    let alive = self.players.values().filter(|p| p.alive).count();
+                    self.start_round(&mut round, alive);
+                }
+            }
+            Phase::Running => {
+                round.advance_zone(dt);
+                self.apply_zone_damage(&round, dt);
+                let survivors: Vec<_> = self.players.values().filter(|p| p.alive).collect();
+                if survivors.len() <= 1 {
+                    let winner = survivors.first().map(|p| (p.user_id, p.name.clone()));
+                    round.winner = winner.as_ref().map(|(_, name)| name.clone());
+                    round.events.push(RoundEvent::Finished {
+                        started_at: std::mem::take(&mut round.started_at),
+                        players: round.entrants,
+                        winner,
+                    });
+                    round.phase = Phase::Finished;
+                    round.timer = round.rules.results_secs as f64;
+                    round.next_zone = None;
+                }
+            }
+            Phase::Finished => {
+                round.timer -= dt;
+                if round.timer <= 0.0 {
+                    let events = std::mem::take(&mut round.events);
+                    round = Round::new();
+                    round.events = events;
+                }
+            }
+        }
+        self.round = Some(round);
+    }
+
+    /// Puts every player back at starting size somewhere random and starts
+    /// the zone
+    fn start_round(&mut self, round: &mut Round, players: usize) {
+        let mut rng = rand::thread_rng();
+        let margin = 200.0;
+        for player in self.players.values_mut().filter(|p| p.alive) {
+            let x = rng.gen_range(margin..WORLD_SIZE - margin);
+            let y = rng.gen_range(margin..WORLD_SIZE - margin);
+            player.cells = vec![Cell::new(x, y, STARTING_MASS)];
+            player.target_x = x;
+            player.target_y = y;
+            player.effects.clear();
+        }
+        self.ejected.clear();
+
+        round.rules = settings().battle_royale.clone();
+        round.phase = Phase::Running;
+        round.zone = Circle::full();
+        round.stage = 0;
+        round.winner = None;
+        round.entrants = players;
+        round.started_at = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        round.begin_stage();
+        round.events.push(RoundEvent::Started { players });
+    }
+
+    /// Shrinks cells outside the zone; a player whose cells all shrink
+    /// away dies
+    fn apply_zone_damage(&mut self, round: &Round, dt: f64) {
+        for player in self.players.values_mut().filter(|p| p.alive) {
+            for cell in &mut player.cells {
+                if !round.zone.contains(cell.x, cell.y) {
+                    let loss = (cell.mass * round.rules.zone_damage).max(ZONE_MIN_DAMAGE);
+                    cell.mass -= loss * dt;
+                }
+            }
+            player.cells.retain(|c| c.mass >= ZONE_KILL_MASS);
+            if player.cells.is_empty() {
+                player.alive = false;
+            }
+        }
+    }
+}
+
+/// Saves finished rounds to the database as the world reports them
+pub async fn record_results(world: SharedWorld, db: SharedDb) {
+    let mut tick = tokio::time::interval(Duration::from_millis(250));
+    loop {
+        tick.tick().await;
+        let events = world.write().await.take_round_events();
+        for event in events {
+            match event {
+                RoundEvent::Started { players } => {
+                    tracing::info!("Battle royale round started with {} players", players);
+                }
+                RoundEvent::Finished {
+                    started_at,
+                    players,
+                    winner,
+                } => {
+                    let name = winner.as_ref().map(|(_, name)| name.clone());
+                    tracing::info!(
+                        "Battle royale round won by {}",
+                        name.as_deref().unwrap_or("nobody")
+                    );
+                    let user_id = winner.and_then(|(uid, _)| uid);
+                    let result = db
+                        .run(move |db| {
+                            db.record_round(&started_at, players, user_id, name.as_deref())
+                        })
+                        .await;
+                    if let Err(e) = result {
+                        tracing::warn!("Failed to record round: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
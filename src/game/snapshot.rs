@@ -0,0 +1,280 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::time::{interval, Instant};
+
+use crate::config::{settings, GameMode};
+use crate::db::SharedDb;
+use crate::game::engine::SharedWorld;
+use crate::game::food::{EjectedMass, Food, Mothercell, PowerUp, Virus};
+use crate::game::player::Player;
+use crate::game::world::World;
+
+/// Bumped whenever the snapshot layout changes. Snapshots written by other
+/// versions are refused rather than half-loaded.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The parts of a `World` that survive a restart
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub players: Vec<Player>,
+    pub food: Vec<Food>,
+    pub viruses: Vec<Virus>,
+    pub ejected: Vec<EjectedMass>,
+    #[serde(default)]
+    pub power_ups: Vec<PowerUp>,
+    #[serde(default)]
+    pub mothercells: Vec<Mothercell>,
+    pub food_target: usize,
+    pub virus_target: usize,
+    pub next_player_id: u64,
+}
+
+/// The file on disk
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    version: u32,
+    saved_at: String,
+    world: WorldSnapshot,
+}
+
+/// Just enough to check the version before parsing the rest
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+}
+
+/// Where snapshots go, if anywhere. Battle royale rounds aren't saved:
+/// a restart ends the round.
+pub fn configured_path() -> Option<String> {
+    let settings = settings();
+    match settings.mode {
+        GameMode::Ffa => settings.snapshot.path.clone(),
+        GameMode::BattleRoyale => None,
+    }
+}
+
+/// Reads a snapshot and when it was saved. A missing file is `Ok(None)`;
+/// an unreadable one or one from another version is an error.
+pub fn load(path: &str) -> Result<Option<(WorldSnapshot, String)>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let header: SnapshotHeader =
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    if header.version != SNAPSHOT_VERSION {
+        return Err(format!(
+            "{} is snapshot version {}, but this server reads version {}",
+            path, header.version, SNAPSHOT_VERSION
+        ));
+    }
+    let file: SnapshotFile = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(Some((file.world, file.saved_at)))
+}
+
+/// Writes a snapshot through a temporary file, so a crash mid-write
+/// leaves the previous snapshot intact.
+fn write(path: &str, world: WorldSnapshot) -> Result<(), String> {
+    let file = SnapshotFile {
+        version: SNAPSHOT_VERSION,
+        saved_at: Utc::now().to_rfc3339(),
+        world,
+    };
+    let text = serde_json::to_string(&file).map_err(|e| format!("Failed to encode snapshot: {}", e))?;
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, text).map_err(|e| format!("{}: {}", tmp, e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Encodes and writes a snapshot off the game's threads. Returns how
+/// many players it holds.
+async fn save(path: &str, snapshot: WorldSnapshot) -> Result<usize, String> {
+    let players = snapshot.players.len();
+    let path = path.to_string();
+    tokio::task::spawn_blocking(move || write(&path, snapshot))
+        .await
+        .map_err(|e| format!("Snapshot task failed: {}", e))??;
+    Ok(players)
+}
+
+/// Accounts among the snapshot's players that recorded a game at or after
+/// `saved_at`. Their runs ended after the save (or may have, within the
+/// same second), so restoring them would record those runs twice.
+async fn ended_since(db: &SharedDb, snapshot: &WorldSnapshot, saved_at: &str) -> Vec<i64> {
+    // Stats are stamped like SQLite's `datetime('now')`, to the second
+    let Ok(saved_at) = DateTime::parse_from_rfc3339(saved_at) else {
+        return Vec::new();
+    };
+    let since = saved_at.with_timezone(&Utc).format("%Y-%m-%d %H:%M:%S").to_string();
+    let user_ids: Vec<i64> = snapshot.players.iter().filter_map(|p| p.user_id).collect();
+    db.run(move |db| {
+        user_ids
+            .into_iter()
+            .filter(|uid| {
+                db.get_stats(*uid)
+                    .and_then(|s| s.updated_at)
+                    .is_some_and(|at| at >= since)
+            })
+            .collect()
+    })
+    .await
+}
+
+/// Builds the starting world: restored from the snapshot at `path` if
+/// there is a usable one, fresh otherwise. A restored snapshot is removed,
+/// so a crash before the next save can't bring its players back again. A
+/// snapshot that can't be restored is moved aside to `<path>.rejected`.
+pub async fn restore(path: &str, db: &SharedDb) -> SharedWorld {
+    let window = Duration::from_secs(settings().snapshot.reconnect_window_secs);
+    let world = match load(path) {
+        Ok(Some((mut snapshot, saved_at))) => {
+            let ended = ended_since(db, &snapshot, &saved_at).await;
+            if !ended.is_empty() {
+                snapshot
+                    .players
+                    .retain(|p| !p.user_id.is_some_and(|uid| ended.contains(&uid)));
+                tracing::info!("Not restoring {} runs that ended after the save", ended.len());
+            }
+            if let Err(e) = std::fs::remove_file(path) {
+                tracing::warn!("Failed to remove restored snapshot {}: {}", path, e);
+            }
+            let world = World::from_snapshot(snapshot, window);
+            println!(
+                "✅ Restored world from {} ({} players waiting to reconnect, {} food, {} viruses)",
+                path,
+                world.players.len(),
+                world.food.len(),
+                world.viruses.len()
+            );
+            world
+        }
+        Ok(None) => World::new(),
+        Err(e) => {
+            tracing::error!("Not restoring snapshot: {}", e);
+            let rejected = format!("{}.rejected", path);
+            match std::fs::rename(path, &rejected) {
+                Ok(()) => println!("⚠️  Unusable snapshot moved to {}; starting fresh", rejected),
+                Err(e) => tracing::warn!("Failed to move {} aside: {}", path, e),
+            }
+            World::new()
+        }
+    };
+    Arc::new(RwLock::new(world))
+}
+
+/// Saves the world for the coming restart and stops runs from being
+/// recorded as players are disconnected. If the save fails any old
+/// snapshot is removed, since it would bring back players whose games are
+/// about to be recorded.
+pub async fn save_for_restart(world: &SharedWorld, path: &str) {
+    let snapshot = {
+        let mut world = world.write().await;
+        world.saved_for_restart = true;
+        world.snapshot()
+    };
+    match save(path, snapshot).await {
+        Ok(players) => println!("💾 Saved world to {} ({} players)", path, players),
+        Err(e) => {
+            tracing::error!("Failed to save snapshot: {}", e);
+            world.write().await.saved_for_restart = false;
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Drops restored players whose owners didn't come back by `now` and
+/// records their games.
+async fn expire_restored(world: &SharedWorld, db: &SharedDb, now: std::time::Instant) {
+    let expired = world.write().await.expire_disconnected(now);
+    let finished: Vec<(i64, u64)> = expired
+        .iter()
+        .filter_map(|p| p.user_id.map(|uid| (uid, p.score)))
+        .collect();
+    if !expired.is_empty() {
+        tracing::info!("{} restored players weren't reclaimed", expired.len());
+    }
+    if !finished.is_empty() {
+        db.run(move |db| {
+            for (user_id, score) in finished {
+                if let Err(e) = db.record_game(user_id, score) {
+                    tracing::warn!("Failed to record stats for user {}: {}", user_id, e);
+                }
+            }
+        })
+        .await;
+    }
+}
+
+/// Background upkeep for snapshots: drops restored players whose owners
+/// didn't come back (recording their games), and saves the world every
+/// `interval_secs` when a path is set.
+pub async fn maintain(world: SharedWorld, db: SharedDb, path: Option<String>) {
+    let mut tick = interval(Duration::from_secs(1));
+    let mut last_save = Instant::now();
+    loop {
+        tick.tick().await;
+        expire_restored(&world, &db, std::time::Instant::now()).await;
+
+        let every = Duration::from_secs(settings().snapshot.interval_secs.max(1));
+        let Some(path) = &path else { continue };
+        if last_save.elapsed() < every {
+            continue;
+        }
+        last_save = Instant::now();
+        // Once shutdown has saved, players leaving mustn't be saved over
+        let snapshot = {
+            let world = world.read().await;
+            (!world.saved_for_restart).then(|| world.snapshot())
+        };
+        if let Some(snapshot) = snapshot {
+            if let Err(e) = save(path, snapshot).await {
+                tracing::warn!("Failed to save snapshot: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::MemoryStorage;
+
+    fn temp_path() -> String {
+        let name = format!("agario-snapshot-{}.json", uuid::Uuid::new_v4());
+        std::env::temp_dir().join(name).to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn expired_players_are_recorded_once() {
+        let db: SharedDb = Arc::new(MemoryStorage::new());
+        let alice = db.register("alice", "secret").unwrap();
+        let bob = db.register("bob", "secret").unwrap();
+        let path = temp_path();
+
+        let mut world = World::new();
+        world.add_player("alice".into(), Some(alice.id));
+        world.add_player("bob".into(), Some(bob.id));
+        write(&path, world.snapshot()).unwrap();
+        // Alice leaves after the save and her run is recorded
+        db.record_game(alice.id, 10).unwrap();
+
+        let world = restore(&path, &db).await;
+        assert!(!std::path::Path::new(&path).exists());
+        let restored: Vec<_> = world.read().await.players.values().map(|p| p.user_id).collect();
+        assert_eq!(restored, [Some(bob.id)]);
+
+        let later = std::time::Instant::now() + Duration::from_secs(24 * 60 * 60);
+        expire_restored(&world, &db, later).await;
+        assert_eq!(db.get_stats(alice.id).map(|s| s.games_played), Some(1));
+        assert_eq!(db.get_stats(bob.id).map(|s| s.games_played), Some(1));
+
+        // A crash now restores nothing, so nobody is recorded again
+        let world = restore(&path, &db).await;
+        assert!(world.read().await.players.is_empty());
+    }
+}
@@ -1,15 +1,63 @@
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use crate::config::*;
-use crate::game::player::{Player, Cell};
-use crate::game::food::{Food, Virus, EjectedMass};
+use crate::game::player::{Player, Cell, SkinVisibility};
+use crate::game::food::{EjectedMass, Food, Mothercell, PowerUp, PowerUpKind, Virus};
 use crate::game::physics;
+use crate::game::round::Round;
+use crate::game::snapshot::WorldSnapshot;
 use rand::Rng;
 
+/// How long each phase of one `World::tick` took, in execution order
+#[derive(Debug, Default, Clone)]
+pub struct TickTimings {
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl TickTimings {
+    /// Records the time since `mark` under `phase` and resets `mark`
+    fn lap(&mut self, phase: &'static str, mark: &mut Instant) {
+        let now = Instant::now();
+        self.phases.push((phase, now - *mark));
+        *mark = now;
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+}
+
+impl std::fmt::Display for TickTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (phase, d)) in self.phases.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {:.2}ms", phase, d.as_secs_f64() * 1000.0)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct World {
     pub players: HashMap<u64, Player>,
     pub food: Vec<Food>,
     pub viruses: Vec<Virus>,
     pub ejected: Vec<EjectedMass>,
+    pub power_ups: Vec<PowerUp>,
+    pub mothercells: Vec<Mothercell>,
+    /// Seconds until each kind of power-up next spawns
+    power_up_timers: HashMap<PowerUpKind, f64>,
+    /// Food and virus counts the world tops itself back up to. Admins can
+    /// change them by spawning or clearing.
+    pub food_target: usize,
+    pub virus_target: usize,
+    /// Set once the world has been saved for a restart. Players leaving
+    /// after that keep their run open for reclaiming instead of having it
+    /// recorded as finished.
+    pub saved_for_restart: bool,
+    /// Battle royale round, when the server runs in that mode
+    pub round: Option<Round>,
     next_player_id: u64,
 }
 
@@ -28,10 +76,95 @@ impl World {
             food,
             viruses,
             ejected: Vec::new(),
+            power_ups: Vec::new(),
+            mothercells: Vec::new(),
+            power_up_timers: HashMap::new(),
+            food_target: FOOD_COUNT,
+            virus_target: VIRUS_COUNT,
+            saved_for_restart: false,
+            round: (settings().mode == GameMode::BattleRoyale).then(Round::new),
             next_player_id: 1,
         }
     }
 
+    /// Copies everything worth keeping across a restart
+    pub fn snapshot(&self) -> WorldSnapshot {
+        let mut players: Vec<Player> = self
+            .players
+            .values()
+            .filter(|p| p.alive && !p.ai)
+            .cloned()
+            .collect();
+        players.sort_by_key(|p| p.id);
+        WorldSnapshot {
+            players,
+            food: self.food.clone(),
+            viruses: self.viruses.clone(),
+            ejected: self.ejected.clone(),
+            power_ups: self.power_ups.clone(),
+            mothercells: self.mothercells.clone(),
+            food_target: self.food_target,
+            virus_target: self.virus_target,
+            next_player_id: self.next_player_id,
+        }
+    }
+
+    /// Rebuilds a world from a snapshot. Its players are disconnected and
+    /// hold still until reclaimed or until `reconnect_window` runs out.
+    pub fn from_snapshot(snapshot: WorldSnapshot, reconnect_window: Duration) -> Self {
+        let reclaim_until = Instant::now() + reconnect_window;
+        let players = snapshot
+            .players
+            .into_iter()
+            .map(|mut p| {
+                (p.target_x, p.target_y) = p.center();
+                p.reclaim_until = Some(reclaim_until);
+                (p.id, p)
+            })
+            .collect();
+        World {
+            players,
+            food: snapshot.food,
+            viruses: snapshot.viruses,
+            ejected: snapshot.ejected,
+            power_ups: snapshot.power_ups,
+            mothercells: snapshot.mothercells,
+            power_up_timers: HashMap::new(),
+            food_target: snapshot.food_target,
+            virus_target: snapshot.virus_target,
+            saved_for_restart: false,
+            round: (settings().mode == GameMode::BattleRoyale).then(Round::new),
+            next_player_id: snapshot.next_player_id,
+        }
+    }
+
+    /// Hands a disconnected player back to whoever holds its resume token,
+    /// logged in to the same account (or to none) as before.
+    pub fn reclaim(&mut self, resume_token: &str, user_id: Option<i64>) -> Option<u64> {
+        let player = self.players.values_mut().find(|p| {
+            p.is_disconnected()
+                && p.alive
+                && p.resume_token == resume_token
+                && p.user_id == user_id
+        })?;
+        player.reclaim_until = None;
+        Some(player.id)
+    }
+
+    /// Removes disconnected players that died or weren't reclaimed in time
+    pub fn expire_disconnected(&mut self, now: Instant) -> Vec<Player> {
+        let expired: Vec<u64> = self
+            .players
+            .values()
+            .filter(|p| p.reclaim_until.is_some_and(|until| !p.alive || until <= now))
+            .map(|p| p.id)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|id| self.players.remove(&id))
+            .collect()
+    }
+
     pub fn add_player(&mut self, name: String, user_id: Option<i64>) -> u64 {
         let id = self.next_player_id;
         self.next_player_id += 1;
@@ -46,27 +179,176 @@ impl World {
         id
     }
 
-    pub fn remove_player(&mut self, id: u64) {
-        self.players.remove(&id);
+    /// Spawns a server-side bot
+    pub fn add_bot(&mut self, name: String) -> u64 {
+        let id = self.add_player(name, None);
+        if let Some(player) = self.players.get_mut(&id) {
+            player.bot = true;
+            player.ai = true;
+        }
+        id
+    }
+
+    /// Moves a freshly spawned player next to another one, for party joins.
+    pub fn spawn_near(&mut self, player_id: u64, other_id: u64) {
+        let Some((cx, cy)) = self.players.get(&other_id).map(|p| p.center()) else {
+            return;
+        };
+        let Some(player) = self.players.get_mut(&player_id) else {
+            return;
+        };
+        let angle = rand::thread_rng().gen_range(0.0..std::f64::consts::TAU);
+        let x = (cx + angle.cos() * FRIEND_SPAWN_DISTANCE).clamp(0.0, WORLD_SIZE);
+        let y = (cy + angle.sin() * FRIEND_SPAWN_DISTANCE).clamp(0.0, WORLD_SIZE);
+        for cell in &mut player.cells {
+            cell.x = x;
+            cell.y = y;
+        }
+        player.target_x = x;
+        player.target_y = y;
+    }
+
+    pub fn remove_player(&mut self, id: u64) -> Option<Player> {
+        self.players.remove(&id)
+    }
+
+    /// Links a guest player to an account without respawning it.
+    pub fn attach_account(
+        &mut self,
+        player_id: u64,
+        user_id: i64,
+        skin: SkinVisibility,
+        verified: bool,
+    ) -> Result<(), String> {
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or_else(|| "Player not found".to_string())?;
+        if player.user_id.is_some() {
+            return Err("Already logged in".into());
+        }
+        player.user_id = Some(user_id);
+        player.skin = skin;
+        player.verified = verified;
+        Ok(())
+    }
+
+    /// Turns every live player of a deleted account into a guest, so
+    /// nothing is recorded against the account when they leave. Returns
+    /// the ids of the players affected.
+    pub fn detach_account(&mut self, user_id: i64) -> Vec<u64> {
+        let mut detached = Vec::new();
+        for player in self.players.values_mut() {
+            if player.user_id == Some(user_id) {
+                player.user_id = None;
+                player.skin = SkinVisibility::Hidden;
+                player.verified = false;
+                detached.push(player.id);
+            }
+        }
+        detached
+    }
+
+    /// Scales a player's cells so their total mass becomes `mass`.
+    pub fn set_player_mass(&mut self, player_id: u64, mass: f64) -> Result<(), String> {
+        if !mass.is_finite() || !(MIN_MASS..=ADMIN_MAX_MASS).contains(&mass) {
+            return Err(format!("Mass must be between {} and {}", MIN_MASS, ADMIN_MAX_MASS));
+        }
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .filter(|p| p.alive)
+            .ok_or_else(|| "Player not found".to_string())?;
+        let factor = mass / player.total_mass();
+        for cell in &mut player.cells {
+            cell.mass = (cell.mass * factor).max(MIN_MASS);
+        }
+        Ok(())
+    }
+
+    /// Adds `count` food pellets and keeps the new total topped up.
+    pub fn spawn_food(&mut self, count: usize) {
+        self.food.extend((0..count).map(|_| Food::random()));
+        self.food_target = self.food.len();
+    }
+
+    /// Removes all food and stops it from respawning until more is spawned.
+    pub fn clear_food(&mut self) {
+        self.food.clear();
+        self.food_target = 0;
+    }
+
+    /// Adds `count` viruses, at `at` or at random positions, and keeps the
+    /// new total topped up.
+    pub fn spawn_viruses(&mut self, count: usize, at: Option<(f64, f64)>) {
+        self.viruses.extend((0..count).map(|_| match at {
+            Some((x, y)) => Virus {
+                x: x.clamp(VIRUS_RADIUS, WORLD_SIZE - VIRUS_RADIUS),
+                y: y.clamp(VIRUS_RADIUS, WORLD_SIZE - VIRUS_RADIUS),
+            },
+            None => Virus::random(),
+        }));
+        self.virus_target = self.viruses.len();
+    }
+
+    /// Removes all viruses and stops them from respawning.
+    pub fn clear_viruses(&mut self) {
+        self.viruses.clear();
+        self.virus_target = 0;
+    }
+
+    /// Updates the skin of every live player belonging to `user_id`
+    pub fn set_skin_visibility(&mut self, user_id: i64, skin: SkinVisibility) {
+        for player in self.players.values_mut() {
+            if player.user_id == Some(user_id) {
+                player.skin = skin;
+            }
+        }
     }
 
-    pub fn tick(&mut self, dt: f64) {
+    pub fn tick(&mut self, dt: f64) -> TickTimings {
+        let mut timings = TickTimings::default();
+        let mut mark = Instant::now();
+
         self.move_players(dt);
+        self.update_ejecting(dt);
         self.move_ejected(dt);
+        self.pull_food(dt);
+        timings.lap("movement", &mut mark);
         self.check_food_eating();
+        timings.lap("eat_food", &mut mark);
         self.check_ejected_eating();
-        self.check_player_eating();
+        timings.lap("eat_ejected", &mut mark);
+        self.check_power_up_pickup();
+        self.update_effects(dt);
+        self.replenish_power_ups(dt);
+        timings.lap("power_ups", &mut mark);
+        if self.pvp_enabled() {
+            self.check_player_eating();
+        }
+        timings.lap("eat_players", &mut mark);
+        self.check_mothercell_eating();
+        self.update_mothercells(dt);
+        timings.lap("mothercells", &mut mark);
         self.check_virus_eating();
+        timings.lap("eat_viruses", &mut mark);
         self.update_merge_timers(dt);
         self.merge_cells();
+        timings.lap("merge", &mut mark);
         self.decay_mass(dt);
         self.replenish_food();
         self.replenish_viruses();
+        timings.lap("decay_replenish", &mut mark);
         self.push_apart_own_cells();
+        timings.lap("push_apart", &mut mark);
+        self.update_round(dt);
+        timings.lap("round", &mut mark);
 
         for player in self.players.values_mut() {
             player.update_score();
         }
+        timings.lap("scores", &mut mark);
+        timings
     }
 
     fn move_players(&mut self, dt: f64) {
@@ -74,6 +356,7 @@ impl World {
             if !player.alive {
                 continue;
             }
+            let boosted = player.has_effect(PowerUpKind::Speed);
             for cell in &mut player.cells {
                 // Apply velocity from split/eject
                 if cell.vx.abs() > 1.0 || cell.vy.abs() > 1.0 {
@@ -89,7 +372,10 @@ impl World {
                     let dy = player.target_y - cell.y;
                     let dist = (dx * dx + dy * dy).sqrt();
                     if dist > 5.0 {
-                        let speed = speed_for_mass(cell.mass) * dt;
+                        let mut speed = speed_for_mass(cell.mass) * dt;
+                        if boosted {
+                            speed *= SPEED_BOOST_MULTIPLIER;
+                        }
                         let (nx, ny) = physics::normalize(dx, dy);
                         cell.x += nx * speed;
                         cell.y += ny * speed;
@@ -103,6 +389,20 @@ impl World {
         }
     }
 
+    /// Counts down eject cooldowns and ejects for players holding the key
+    fn update_ejecting(&mut self, dt: f64) {
+        let mut due = Vec::new();
+        for player in self.players.values_mut() {
+            player.eject_cooldown = (player.eject_cooldown - dt).max(0.0);
+            if player.ejecting && player.alive && player.eject_cooldown <= 0.0 {
+                due.push(player.id);
+            }
+        }
+        for id in due {
+            self.request_eject(id);
+        }
+    }
+
     fn move_ejected(&mut self, dt: f64) {
         for ej in &mut self.ejected {
             ej.x += ej.vx * dt;
@@ -147,12 +447,13 @@ impl World {
             }
             for cell in &mut player.cells {
                 for (ei, ej) in self.ejected.iter().enumerate() {
-                    if ej.vx.abs() < 5.0 && ej.vy.abs() < 5.0 {
-                        if physics::can_eat_food(cell.x, cell.y, cell.mass, ej.x, ej.y) {
-                            cell.mass += ej.mass;
-                            if !eaten_indices.contains(&ei) {
-                                eaten_indices.push(ei);
-                            }
+                    if ej.vx.abs() < 5.0
+                        && ej.vy.abs() < 5.0
+                        && physics::can_eat_food(cell.x, cell.y, cell.mass, ej.x, ej.y)
+                    {
+                        cell.mass += ej.mass;
+                        if !eaten_indices.contains(&ei) {
+                            eaten_indices.push(ei);
                         }
                     }
                 }
@@ -164,6 +465,86 @@ impl World {
         }
     }
 
+    /// Players with a magnet pull nearby food toward their closest cell
+    fn pull_food(&mut self, dt: f64) {
+        let magnets: Vec<(f64, f64, f64)> = self
+            .players
+            .values()
+            .filter(|p| p.alive && p.has_effect(PowerUpKind::Magnet))
+            .flat_map(|p| p.cells.iter().map(|c| (c.x, c.y, c.radius())))
+            .collect();
+        if magnets.is_empty() {
+            return;
+        }
+        let step = MAGNET_PULL_SPEED * dt;
+        for food in &mut self.food {
+            let nearest = magnets
+                .iter()
+                .map(|&(x, y, r)| (x, y, physics::distance(x, y, food.x, food.y) - r))
+                .min_by(|a, b| a.2.total_cmp(&b.2));
+            if let Some((x, y, gap)) = nearest {
+                if gap < MAGNET_RANGE {
+                    let (nx, ny) = physics::normalize(x - food.x, y - food.y);
+                    let step = step.min(gap.max(0.0) + FOOD_RADIUS);
+                    food.x += nx * step;
+                    food.y += ny * step;
+                }
+            }
+        }
+    }
+
+    /// Touching a power-up starts its effect, or restarts it if it's
+    /// already running
+    fn check_power_up_pickup(&mut self) {
+        let rules = settings().power_ups.clone();
+        let mut taken = Vec::new();
+        for (i, power_up) in self.power_ups.iter().enumerate() {
+            let picker = self.players.values_mut().find(|p| {
+                p.alive
+                    && p.cells.iter().any(|c| {
+                        physics::distance(c.x, c.y, power_up.x, power_up.y)
+                            < c.radius() + POWER_UP_RADIUS
+                    })
+            });
+            if let Some(player) = picker {
+                let duration = power_up.kind.rule(&rules).duration_secs;
+                player.effects.insert(power_up.kind, duration);
+                taken.push(i);
+            }
+        }
+        for i in taken.into_iter().rev() {
+            self.power_ups.swap_remove(i);
+        }
+    }
+
+    fn update_effects(&mut self, dt: f64) {
+        for player in self.players.values_mut() {
+            player.effects.retain(|_, left| {
+                *left -= dt;
+                *left > 0.0
+            });
+        }
+    }
+
+    /// Spawns each kind of power-up on its own timer, up to its cap
+    fn replenish_power_ups(&mut self, dt: f64) {
+        let rules = settings().power_ups.clone();
+        for kind in PowerUpKind::ALL {
+            let rule = kind.rule(&rules);
+            let on_map = self.power_ups.iter().filter(|p| p.kind == kind).count();
+            let timer = self.power_up_timers.entry(kind).or_insert(rule.spawn_secs);
+            if on_map >= rule.max_count {
+                *timer = rule.spawn_secs;
+                continue;
+            }
+            *timer -= dt;
+            if *timer <= 0.0 {
+                *timer = rule.spawn_secs;
+                self.power_ups.push(PowerUp::random(kind));
+            }
+        }
+    }
+
     fn check_player_eating(&mut self) {
         let ids: Vec<u64> = self.players.keys().cloned().collect();
         let mut kills: Vec<(u64, u64, usize, f64)> = Vec::new(); // (eater_id, victim_id, victim_cell_idx, mass)
@@ -178,7 +559,7 @@ impl World {
                     _ => continue,
                 };
                 let p2 = match self.players.get(&id2) {
-                    Some(p) if p.alive => p,
+                    Some(p) if p.alive && !p.has_effect(PowerUpKind::Shield) => p,
                     _ => continue,
                 };
 
@@ -227,6 +608,49 @@ impl World {
         }
     }
 
+    /// Cells big enough to eat a mothercell swallow it whole
+    fn check_mothercell_eating(&mut self) {
+        let mut eaten = Vec::new();
+        for (mi, mother) in self.mothercells.iter().enumerate() {
+            let eater = self.players.values_mut().filter(|p| p.alive).find_map(|p| {
+                p.cells.iter_mut().find(|c| {
+                    physics::can_eat(c.x, c.y, c.mass, mother.x, mother.y, mother.mass)
+                })
+            });
+            if let Some(cell) = eater {
+                cell.mass += mother.mass;
+                eaten.push(mi);
+            }
+        }
+        for i in eaten.into_iter().rev() {
+            self.mothercells.swap_remove(i);
+        }
+    }
+
+    /// Grows mothercells, lets them burst into food, and keeps the map at
+    /// this mode's mothercell count
+    fn update_mothercells(&mut self, dt: f64) {
+        let settings = settings();
+        let rule = settings.mothercells.for_mode(settings.mode);
+        let food_cap = self.food_target + MOTHERCELL_MAX_EXTRA_FOOD;
+        for mother in &mut self.mothercells {
+            if mother.mass < rule.max_mass {
+                mother.mass = (mother.mass + rule.growth_per_sec * dt).min(rule.max_mass);
+            }
+            mother.burst_timer -= dt;
+            if mother.burst_timer <= 0.0 {
+                mother.burst_timer = rule.burst_secs;
+                if self.food.len() < food_cap {
+                    self.food.extend(mother.burst(rule));
+                }
+            }
+        }
+        self.mothercells.truncate(rule.count);
+        while self.mothercells.len() < rule.count {
+            self.mothercells.push(Mothercell::random(rule));
+        }
+    }
+
     fn check_virus_eating(&mut self) {
         let mut virus_eaten = Vec::new();
         for player in self.players.values_mut() {
@@ -271,9 +695,14 @@ impl World {
 
     fn update_merge_timers(&mut self, dt: f64) {
         for player in self.players.values_mut() {
+            let step = if player.has_effect(PowerUpKind::Merge) {
+                dt * FAST_MERGE_MULTIPLIER
+            } else {
+                dt
+            };
             for cell in &mut player.cells {
                 if cell.merge_time > 0.0 {
-                    cell.merge_time -= dt;
+                    cell.merge_time -= step;
                 }
             }
         }
@@ -358,17 +787,40 @@ impl World {
     }
 
     fn replenish_food(&mut self) {
-        while self.food.len() < FOOD_COUNT {
+        while self.food.len() < self.food_target {
             self.food.push(Food::random());
         }
     }
 
     fn replenish_viruses(&mut self) {
-        while self.viruses.len() < VIRUS_COUNT {
+        while self.viruses.len() < self.virus_target {
             self.viruses.push(Virus::random());
         }
     }
 
+    /// Handles a player's split request: `times` successive splits, all
+    /// done before the next tick moves anything. Returns false, doing
+    /// nothing, when the request comes within `SPLIT_COOLDOWN_SECS` of the
+    /// last one.
+    pub fn request_split(&mut self, player_id: u64, times: u32) -> bool {
+        let Some(player) = self.players.get_mut(&player_id) else {
+            return true;
+        };
+        let now = Instant::now();
+        let cooldown = Duration::from_secs_f64(SPLIT_COOLDOWN_SECS);
+        if player.last_split.is_some_and(|last| now - last < cooldown) {
+            return false;
+        }
+        player.last_split = Some(now);
+        for _ in 0..times.clamp(1, MAX_SPLIT_TIMES) {
+            if !self.players.get(&player_id).is_some_and(|p| p.can_split()) {
+                break;
+            }
+            self.split_player(player_id);
+        }
+        true
+    }
+
     pub fn split_player(&mut self, player_id: u64) {
         let player = match self.players.get_mut(&player_id) {
             Some(p) if p.alive => p,
@@ -388,8 +840,16 @@ impl World {
                 cell.mass = half;
                 cell.merge_time = MERGE_TIME_SECS;
 
-                let (nx, ny) = physics::normalize(tx - cell.x, ty - cell.y);
-                let mut new_cell = Cell::new(cell.x, cell.y, half);
+                // Toward the cursor; a cell sitting on it keeps going the
+                // way it's already flying
+                let (mut nx, mut ny) = physics::normalize(tx - cell.x, ty - cell.y);
+                if (nx, ny) == (0.0, 0.0) {
+                    (nx, ny) = physics::normalize(cell.vx, cell.vy);
+                }
+                // Launched from the parent's leading edge, so successive
+                // splits in one request line up instead of stacking
+                let r = cell.radius();
+                let mut new_cell = Cell::new(cell.x + nx * r, cell.y + ny * r, half);
                 new_cell.vx = nx * SPLIT_LAUNCH_SPEED;
                 new_cell.vy = ny * SPLIT_LAUNCH_SPEED;
                 new_cell.merge_time = MERGE_TIME_SECS;
@@ -399,6 +859,22 @@ impl World {
         player.cells.extend(new_cells);
     }
 
+    /// Ejects once if the player's cooldown allows it, then restarts the
+    /// cooldown. Returns false when the request came too soon.
+    pub fn request_eject(&mut self, player_id: u64) -> bool {
+        let Some(player) = self.players.get_mut(&player_id) else {
+            return true;
+        };
+        if player.eject_cooldown > 0.0 {
+            return false;
+        }
+        player.eject_cooldown = EJECT_INTERVAL_SECS;
+        self.eject_mass(player_id);
+        true
+    }
+
+    /// Ejects a pellet from every cell big enough, each turned a little at
+    /// random so pellets don't stack
     pub fn eject_mass(&mut self, player_id: u64) {
         let player = match self.players.get_mut(&player_id) {
             Some(p) if p.alive => p,
@@ -410,11 +886,15 @@ impl World {
 
         let (tx, ty) = (player.target_x, player.target_y);
         let color = player.color.clone();
+        let mut rng = rand::thread_rng();
 
         for cell in player.cells.iter_mut() {
             if cell.mass >= EJECT_MIN_MASS {
                 cell.mass -= EJECT_MASS;
-                let (nx, ny) = physics::normalize(tx - cell.x, ty - cell.y);
+                let (dx, dy) = physics::normalize(tx - cell.x, ty - cell.y);
+                let turn = rng.gen_range(-EJECT_SPREAD..=EJECT_SPREAD);
+                let (sin, cos) = turn.sin_cos();
+                let (nx, ny) = (dx * cos - dy * sin, dx * sin + dy * cos);
                 let r = cell.radius();
                 self.ejected.push(EjectedMass {
                     x: cell.x + nx * r,
@@ -424,7 +904,6 @@ impl World {
                     vy: ny * EJECT_SPEED,
                     color: color.clone(),
                 });
-                break; // Only eject from one cell
             }
         }
     }
@@ -436,7 +915,7 @@ impl World {
             .filter(|p| p.alive)
             .map(|p| (p.name.clone(), p.total_mass() as u64))
             .collect();
-        entries.sort_by(|a, b| b.1.cmp(&a.1));
+        entries.sort_by_key(|e| std::cmp::Reverse(e.1));
         entries.truncate(10);
         entries
     }
@@ -1,57 +1,136 @@
+mod cli;
 mod config;
 mod db;
 mod game;
+mod metrics;
+mod names;
 mod protocol;
 mod server;
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use axum::{routing::get, Router};
+use clap::Parser;
 use tower_http::services::ServeDir;
-use tracing_subscriber;
 
-use crate::config::SERVER_PORT;
-use crate::db::Database;
-use crate::game::engine;
+use crate::cli::{Cli, Command, ServeArgs};
+use crate::db::memory::MemoryStorage;
+use crate::db::SharedDb;
+use crate::game::{bots, engine, round, snapshot};
 use crate::server::http;
+use crate::server::admin;
+use crate::server::connections::Connections;
+use crate::server::console::Console;
+use crate::server::presence::Presence;
+use crate::server::ratelimit::RateLimiter;
+use crate::server::shutdown;
 use crate::server::ws;
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
     tracing_subscriber::fmt::init();
 
-    // Ensure data directory exists
-    std::fs::create_dir_all("data").ok();
+    // Load runtime settings; account commands need the name rules too
+    if let Err(e) = config::init_settings(&cli.config) {
+        eprintln!("❌ Invalid settings file: {}", e);
+        std::process::exit(1);
+    }
 
-    // Initialize database
-    let db = Arc::new(Database::new("data/game.db"));
+    match cli.command.unwrap_or(Command::Serve(cli.serve)) {
+        Command::Serve(args) => serve(args, &cli.db).await,
+        command => {
+            if let Err(e) = cli::run(command, &cli.db) {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+async fn serve(args: ServeArgs, db_path: &str) {
+    // Initialize storage (STORAGE=memory for a throwaway server)
+    let db: SharedDb = if std::env::var("STORAGE").as_deref() == Ok("memory") {
+        println!("⚠️  Using in-memory storage; nothing will be saved");
+        Arc::new(MemoryStorage::new())
+    } else {
+        Arc::new(cli::open_database(db_path))
+    };
     println!("✅ Database initialized");
+    admin::promote_configured_admins(&db).await;
 
-    // Create game world
-    let world = engine::create_world();
-    println!("✅ Game world created ({}x{})", config::WORLD_SIZE, config::WORLD_SIZE);
+    // Create the game world, or bring back the one saved before a restart
+    let snapshot_path = snapshot::configured_path();
+    let world = match &snapshot_path {
+        Some(path) => snapshot::restore(path, &db).await,
+        None => engine::create_world(),
+    };
+    println!("✅ Game world ready ({}x{})", config::WORLD_SIZE, config::WORLD_SIZE);
+    tokio::spawn(snapshot::maintain(world.clone(), db.clone(), snapshot_path));
 
-    // Start game loop
-    let world_clone = world.clone();
+    // Start game loop; it only ever ends by panicking, which /readyz reports
+    let game_loop = tokio::spawn(engine::game_loop(world.clone()));
     tokio::spawn(async move {
-        engine::game_loop(world_clone).await;
+        if let Err(e) = game_loop.await {
+            tracing::error!("Game loop crashed: {}", e);
+            engine::LOOP_STATUS.mark_crashed();
+        }
     });
     println!("✅ Game loop running at {} TPS", config::TICK_RATE);
+    tokio::spawn(bots::run(world.clone(), db.clone()));
+    if config::settings().mode == config::GameMode::BattleRoyale {
+        tokio::spawn(round::record_results(world.clone(), db.clone()));
+        println!("✅ Battle royale mode");
+    }
 
     // WebSocket state
+    let presence = Arc::new(Presence::new());
+    let connections = Arc::new(Connections::new());
     let ws_state = ws::WsState {
         world: world.clone(),
         db: db.clone(),
+        presence: presence.clone(),
+        connections: connections.clone(),
     };
 
     // Build router
     let app = Router::new()
         .route("/ws", get(ws::ws_handler).with_state(ws_state))
-        .merge(http::api_routes(db))
-        .fallback_service(ServeDir::new("static"));
+        .merge(http::api_routes(http::ApiState {
+            db: db.clone(),
+            limiter: Arc::new(RateLimiter::new()),
+            world: world.clone(),
+            presence,
+            connections: connections.clone(),
+        }))
+        .fallback_service(ServeDir::new(&args.static_dir));
 
-    let addr = format!("0.0.0.0:{}", SERVER_PORT);
-    println!("🎮 Agar.io clone running at http://localhost:{}", SERVER_PORT);
+    // Operator console on stdin, and optionally a Unix socket
+    let console = Console {
+        world: world.clone(),
+        db: db.clone(),
+        connections: connections.clone(),
+    };
+    #[cfg(unix)]
+    if let Some(path) = config::settings().console_socket.clone() {
+        tokio::spawn(console.clone().run_socket(path));
+    }
+    tokio::spawn(console.run_stdin());
+
+    let addr = SocketAddr::new(args.bind, args.port);
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("❌ Can't listen on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    let host = if args.bind.is_unspecified() { "localhost".to_string() } else { addr.ip().to_string() };
+    println!("🎮 Agar.io clone running at http://{}:{}", host, args.port);
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown::on_signal(world, db, connections))
+        .await
+        .unwrap();
+    println!("👋 Server stopped");
 }
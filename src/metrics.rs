@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the game tick histogram buckets
+const TICK_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.02, 0.033, 0.05, 0.1, 0.25];
+/// Upper bounds (seconds) of the database histogram buckets
+const DB_BUCKETS: &[f64] = &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0];
+/// Upper bounds (seconds) of the password hashing histogram buckets
+const HASH_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+const MAX_BUCKETS: usize = 12;
+
+/// Process-wide counters, rendered in Prometheus text format by `GET /metrics`.
+pub struct Metrics {
+    pub rate_limited_ip: AtomicU64,
+    pub rate_limited_username: AtomicU64,
+    pub lockouts: AtomicU64,
+    pub logins_succeeded: AtomicU64,
+    pub logins_failed: AtomicU64,
+    pub logins_banned: AtomicU64,
+    /// Open WebSocket connections, joined or not
+    pub sockets: AtomicU64,
+    /// State frames skipped because the client's outbound queue was full
+    pub queue_drops: AtomicU64,
+    /// Split requests ignored for coming too soon after the last one
+    pub splits_limited: AtomicU64,
+    /// Eject requests ignored for coming faster than the eject rate
+    pub ejects_limited: AtomicU64,
+    pub tick_duration: Histogram,
+    /// Ticks that took longer than their time slot
+    pub tick_overruns: AtomicU64,
+    /// Ticks dropped because the loop fell too far behind to catch up
+    pub ticks_skipped: AtomicU64,
+    /// Total nanoseconds spent in each phase of the world tick
+    tick_phases: Mutex<BTreeMap<&'static str, u64>>,
+    /// Time a pooled connection is checked out, i.e. spent on queries
+    pub db_duration: Histogram,
+    /// Time spent waiting for a free pooled connection
+    pub db_pool_wait: Histogram,
+    /// Argon2 hashing and verification, which runs beside storage calls
+    pub password_hash_duration: Histogram,
+    /// Messages and bytes sent, by `ServerMessage` type
+    sent: Mutex<BTreeMap<&'static str, (u64, u64)>>,
+}
+
+pub static METRICS: Metrics = Metrics {
+    rate_limited_ip: AtomicU64::new(0),
+    rate_limited_username: AtomicU64::new(0),
+    lockouts: AtomicU64::new(0),
+    logins_succeeded: AtomicU64::new(0),
+    logins_failed: AtomicU64::new(0),
+    logins_banned: AtomicU64::new(0),
+    sockets: AtomicU64::new(0),
+    queue_drops: AtomicU64::new(0),
+    splits_limited: AtomicU64::new(0),
+    ejects_limited: AtomicU64::new(0),
+    tick_duration: Histogram::new(TICK_BUCKETS),
+    tick_overruns: AtomicU64::new(0),
+    ticks_skipped: AtomicU64::new(0),
+    tick_phases: Mutex::new(BTreeMap::new()),
+    db_duration: Histogram::new(DB_BUCKETS),
+    db_pool_wait: Histogram::new(DB_BUCKETS),
+    password_hash_duration: Histogram::new(HASH_BUCKETS),
+    sent: Mutex::new(BTreeMap::new()),
+};
+
+pub fn inc(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Increments a gauge and decrements it again when dropped.
+pub struct GaugeGuard(&'static AtomicU64);
+
+impl GaugeGuard {
+    pub fn new(gauge: &'static AtomicU64) -> Self {
+        inc(gauge);
+        GaugeGuard(gauge)
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A Prometheus histogram with fixed buckets
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Per-bucket (non-cumulative) counts; the last used slot is `+Inf`
+    buckets: [AtomicU64; MAX_BUCKETS],
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    const fn new(bounds: &'static [f64]) -> Self {
+        assert!(bounds.len() < MAX_BUCKETS);
+        Histogram {
+            bounds,
+            buckets: [const { AtomicU64::new(0) }; MAX_BUCKETS],
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, secs: f64) {
+        let slot = self
+            .bounds
+            .iter()
+            .position(|b| secs <= *b)
+            .unwrap_or(self.bounds.len());
+        self.buckets[slot].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add((secs * 1e9) as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, exp: &mut Exposition, name: &str, help: &str) {
+        exp.family(name, "histogram", help);
+        let bucket = format!("{}_bucket", name);
+        let mut cumulative = 0;
+        for (i, bound) in self.bounds.iter().enumerate() {
+            cumulative += self.buckets[i].load(Ordering::Relaxed);
+            exp.sample(&bucket, &[("le", &bound.to_string())], cumulative as f64);
+        }
+        cumulative += self.buckets[self.bounds.len()].load(Ordering::Relaxed);
+        exp.sample(&bucket, &[("le", "+Inf")], cumulative as f64);
+        exp.sample(
+            &format!("{}_sum", name),
+            &[],
+            self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+        );
+        exp.sample(&format!("{}_count", name), &[], cumulative as f64);
+    }
+}
+
+/// Builder for the Prometheus text exposition format.
+#[derive(Default)]
+pub struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) -> &mut Self {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+        self
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            self.out.push('{');
+            for (i, (k, v)) in labels.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                let v = v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+                let _ = write!(self.out, "{}=\"{}\"", k, v);
+            }
+            self.out.push('}');
+        }
+        let _ = writeln!(self.out, " {}", value);
+        self
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+impl Metrics {
+    pub fn record_sent(&self, kind: &'static str, bytes: usize) {
+        let mut sent = self.sent.lock().unwrap();
+        let entry = sent.entry(kind).or_default();
+        entry.0 += 1;
+        entry.1 += bytes as u64;
+    }
+
+    pub fn record_tick_phases<'a>(&self, phases: impl Iterator<Item = &'a (&'static str, Duration)>) {
+        let mut totals = self.tick_phases.lock().unwrap();
+        for (phase, d) in phases {
+            *totals.entry(phase).or_default() += d.as_nanos() as u64;
+        }
+    }
+
+    pub fn render(&self, exp: &mut Exposition) {
+        exp.family(
+            "auth_rate_limited_total",
+            "counter",
+            "Login/register attempts rejected by the rate limiter",
+        )
+        .sample(
+            "auth_rate_limited_total",
+            &[("scope", "ip")],
+            self.rate_limited_ip.load(Ordering::Relaxed) as f64,
+        )
+        .sample(
+            "auth_rate_limited_total",
+            &[("scope", "username")],
+            self.rate_limited_username.load(Ordering::Relaxed) as f64,
+        );
+        exp.family(
+            "auth_lockouts_total",
+            "counter",
+            "Temporary lockouts issued after repeated failures",
+        )
+        .sample("auth_lockouts_total", &[], self.lockouts.load(Ordering::Relaxed) as f64);
+        exp.family("auth_logins_total", "counter", "Login attempts by outcome")
+            .sample(
+                "auth_logins_total",
+                &[("result", "success")],
+                self.logins_succeeded.load(Ordering::Relaxed) as f64,
+            )
+            .sample(
+                "auth_logins_total",
+                &[("result", "failure")],
+                self.logins_failed.load(Ordering::Relaxed) as f64,
+            )
+            .sample(
+                "auth_logins_total",
+                &[("result", "banned")],
+                self.logins_banned.load(Ordering::Relaxed) as f64,
+            );
+
+        exp.family("ws_connections", "gauge", "Open WebSocket connections")
+            .sample("ws_connections", &[], self.sockets.load(Ordering::Relaxed) as f64);
+        exp.family(
+            "ws_queue_drops_total",
+            "counter",
+            "State updates dropped because a client's outbound queue was full",
+        )
+        .sample("ws_queue_drops_total", &[], self.queue_drops.load(Ordering::Relaxed) as f64);
+        exp.family(
+            "ws_inputs_rate_limited_total",
+            "counter",
+            "Player inputs ignored for coming too fast, by action",
+        )
+        .sample(
+            "ws_inputs_rate_limited_total",
+            &[("action", "split")],
+            self.splits_limited.load(Ordering::Relaxed) as f64,
+        )
+        .sample(
+            "ws_inputs_rate_limited_total",
+            &[("action", "eject")],
+            self.ejects_limited.load(Ordering::Relaxed) as f64,
+        );
+        {
+            let sent = self.sent.lock().unwrap();
+            exp.family("ws_messages_sent_total", "counter", "Messages queued to clients, by type");
+            for (kind, (messages, _)) in sent.iter() {
+                exp.sample("ws_messages_sent_total", &[("type", kind)], *messages as f64);
+            }
+            exp.family("ws_bytes_sent_total", "counter", "Bytes queued to clients, by message type");
+            for (kind, (_, bytes)) in sent.iter() {
+                exp.sample("ws_bytes_sent_total", &[("type", kind)], *bytes as f64);
+            }
+        }
+
+        self.tick_duration.render(
+            exp,
+            "game_tick_duration_seconds",
+            "Time spent simulating one world tick",
+        );
+        exp.family(
+            "game_tick_overruns_total",
+            "counter",
+            "Ticks that took longer than their time slot",
+        )
+        .sample("game_tick_overruns_total", &[], self.tick_overruns.load(Ordering::Relaxed) as f64);
+        exp.family(
+            "game_ticks_skipped_total",
+            "counter",
+            "Ticks dropped after a stall because catch-up was capped",
+        )
+        .sample("game_ticks_skipped_total", &[], self.ticks_skipped.load(Ordering::Relaxed) as f64);
+        {
+            let phases = self.tick_phases.lock().unwrap();
+            exp.family(
+                "game_tick_phase_seconds_total",
+                "counter",
+                "Time spent in each phase of the world tick",
+            );
+            for (phase, nanos) in phases.iter() {
+                exp.sample(
+                    "game_tick_phase_seconds_total",
+                    &[("phase", phase)],
+                    *nanos as f64 / 1e9,
+                );
+            }
+        }
+        self.db_duration.render(
+            exp,
+            "db_query_duration_seconds",
+            "Time a database connection is in use per storage call",
+        );
+        self.db_pool_wait.render(
+            exp,
+            "db_pool_wait_seconds",
+            "Time spent waiting for a free database connection",
+        );
+        self.password_hash_duration.render(
+            exp,
+            "auth_password_hash_duration_seconds",
+            "Time spent hashing and verifying passwords",
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Username and display-name policy.
+//!
+//! Names are NFKC-normalized with invisible characters removed before they
+//! are checked or stored. Comparisons use [`name_key`], which folds case and
+//! confusable characters so that "Alice", "ALICE" and "AIice" all collide.
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{skeleton, GeneralSecurityProfile, MixedScript};
+
+use crate::config::settings;
+
+pub const MIN_NAME_LEN: usize = 2;
+pub const MAX_NAME_LEN: usize = 20;
+/// Shown for players who leave the name field empty
+pub const DEFAULT_NAME: &str = "Unnamed";
+
+/// Zero-width, bidi-control and filler characters that render as nothing
+/// (or reorder text) and are used to dodge filters or fake names.
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{034F}'
+                | '\u{061C}'
+                | '\u{115F}'
+                | '\u{1160}'
+                | '\u{17B4}'
+                | '\u{17B5}'
+                | '\u{180B}'..='\u{180F}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{206F}'
+                | '\u{3164}'
+                | '\u{FE00}'..='\u{FE0F}'
+                | '\u{FEFF}'
+                | '\u{FFA0}'
+                | '\u{E0000}'..='\u{E007F}'
+        )
+}
+
+/// NFKC-normalizes a name, drops invisible characters and collapses runs of
+/// whitespace into a single space.
+pub fn normalize(name: &str) -> String {
+    let cleaned: String = name
+        .nfkc()
+        .filter(|c| !is_invisible(*c))
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    cleaned.split(' ').filter(|w| !w.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// Comparison key: confusable skeleton, lowercased, alphanumerics only.
+pub fn name_key(name: &str) -> String {
+    let folded: String = skeleton(&normalize(name)).collect::<String>().to_lowercase();
+    // The skeleton maps capital I to l but leaves lowercase i alone, so fold
+    // the two together to make the key case-insensitive
+    skeleton(&folded)
+        .flat_map(char::to_lowercase)
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| if c == 'i' { 'l' } else { c })
+        .collect()
+}
+
+/// Rejects reserved names and names containing a blocked word.
+fn check_policy(name: &str) -> Result<(), String> {
+    let key = name_key(name);
+    let policy = &settings().names;
+    if policy.reserved.iter().any(|r| name_key(r) == key) {
+        return Err("That name is reserved".into());
+    }
+    if contains_blocked_word(name, &policy.blocked_words) {
+        return Err("That name isn't allowed".into());
+    }
+    Ok(())
+}
+
+/// Splits a name into words at separators, letter/digit changes and
+/// lower-to-upper case changes, so "xX_bad_Xx", "Bad99" and "someBad" all
+/// yield "bad" as a word, but "Scunthorpe" stays a single word.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<char> = None;
+    for c in normalize(name).chars() {
+        if !c.is_alphanumeric() {
+            words.push(std::mem::take(&mut current));
+            prev = None;
+            continue;
+        }
+        if let Some(p) = prev {
+            let boundary = p.is_alphabetic() != c.is_alphabetic()
+                || (p.is_lowercase() && c.is_uppercase());
+            if boundary {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(c);
+        prev = Some(c);
+    }
+    words.push(current);
+    words.iter().map(|w| name_key(w)).filter(|w| !w.is_empty()).collect()
+}
+
+/// Whether any run of whole words in `name` spells a blocked word. Blocked
+/// words hidden inside a longer word are allowed.
+fn contains_blocked_word(name: &str, blocked: &[String]) -> bool {
+    let words = words(name);
+    blocked.iter().map(|w| name_key(w)).any(|w| {
+        !w.is_empty()
+            && (0..words.len()).any(|start| {
+                let mut run = String::new();
+                words[start..].iter().any(|word| {
+                    run.push_str(word);
+                    run == w
+                })
+            })
+    })
+}
+
+/// Validates a username for registration and returns its normalized form.
+pub fn validate_username(username: &str) -> Result<String, String> {
+    let name = normalize(username);
+    let len = name.chars().count();
+    if !(MIN_NAME_LEN..=MAX_NAME_LEN).contains(&len) {
+        return Err(format!("Username must be {}-{} characters", MIN_NAME_LEN, MAX_NAME_LEN));
+    }
+    let allowed = name
+        .chars()
+        .all(|c| matches!(c, '_' | '-' | '.') || (c.is_alphanumeric() && c.identifier_allowed()));
+    if !allowed {
+        return Err("Username may only contain letters, digits, '_', '-' and '.'".into());
+    }
+    if !name.as_str().is_single_script() {
+        return Err("Username can't mix alphabets".into());
+    }
+    if name_key(&name).is_empty() {
+        return Err("Username must contain a letter or digit".into());
+    }
+    check_policy(&name)?;
+    Ok(name)
+}
+
+/// Cleans up an in-game display name. Empty names become [`DEFAULT_NAME`].
+/// Impersonation of registered accounts is checked separately, against the
+/// database.
+pub fn display_name(name: &str) -> Result<String, String> {
+    let name: String = normalize(name).chars().take(MAX_NAME_LEN).collect();
+    let name = name.trim_end();
+    if name.is_empty() {
+        return Ok(DEFAULT_NAME.to_string());
+    }
+    check_policy(name)?;
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked(name: &str) -> bool {
+        contains_blocked_word(name, &["bad".to_string(), "very bad".to_string()])
+    }
+
+    #[test]
+    fn blocked_words_match_whole_words() {
+        assert!(blocked("bad"));
+        assert!(blocked("BAD"));
+        assert!(blocked("xX_bad_Xx"));
+        assert!(blocked("the bad guy"));
+        assert!(blocked("Bad99"));
+        assert!(blocked("someBad"));
+        assert!(blocked("b\u{200B}ad"));
+        assert!(blocked("very_bad"));
+    }
+
+    #[test]
+    fn blocked_words_inside_other_words_are_allowed() {
+        assert!(!blocked("badger"));
+        assert!(!blocked("Sinbad"));
+        assert!(!blocked("abadon"));
+        assert!(!blocked("good"));
+    }
+}
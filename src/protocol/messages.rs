@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::metrics::METRICS;
+
 // ── Client → Server ──
 
 #[derive(Debug, Deserialize)]
@@ -7,15 +9,39 @@ use serde::{Deserialize, Serialize};
 pub enum ClientMessage {
     Join {
         name: String,
+        /// Session token, or an API token with the `play` scope
         #[serde(default)]
         token: Option<String>,
+        /// Account id of a friend to spawn next to
+        #[serde(default)]
+        friend: Option<i64>,
+        /// `resume_token` from an earlier `joined`, to take back a player
+        /// restored after a server restart
+        #[serde(default)]
+        resume: Option<String>,
     },
     Move {
         x: f64,
         y: f64,
     },
-    Split,
+    Split {
+        /// Successive splits to do at once, e.g. 2 for a double split.
+        /// Clamped to 1..=`MAX_SPLIT_TIMES`.
+        #[serde(default = "one")]
+        times: u32,
+    },
     Eject,
+    /// Keep ejecting at the server's rate until `EjectStop`
+    EjectStart,
+    EjectStop,
+    /// Attach a freshly logged-in account to the current (guest) player
+    Attach {
+        token: String,
+    },
+}
+
+fn one() -> u32 {
+    1
 }
 
 // ── Server → Client ──
@@ -26,22 +52,85 @@ pub enum ServerMessage {
     Joined {
         id: u64,
         world_size: f64,
+        room: String,
+        /// Keep this to reclaim the player if the server restarts
+        resume_token: String,
+        /// Took back a player restored from before a restart
+        resumed: bool,
     },
     State {
         players: Vec<PlayerState>,
         food: Vec<FoodState>,
         viruses: Vec<VirusState>,
+        power_ups: Vec<PowerUpState>,
+        mothercells: Vec<MothercellState>,
         leaderboard: Vec<LeaderboardEntry>,
+        /// Battle royale round; absent in free-for-all
+        #[serde(skip_serializing_if = "Option::is_none")]
+        round: Option<RoundState>,
     },
     Dead {
         killer: String,
         score: u64,
     },
+    Attached {
+        user_id: i64,
+        username: String,
+    },
+    /// Message from the server operators, shown to everyone
+    Announcement {
+        message: String,
+    },
+    /// Sent right before the server closes the connection
+    Kicked {
+        reason: String,
+    },
+    /// The server is going down in `seconds`
+    Shutdown {
+        seconds: u64,
+    },
     Error {
         message: String,
+        /// Set when the client is banned
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ban: Option<BanNotice>,
     },
 }
 
+impl ServerMessage {
+    /// The `type` tag, for metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServerMessage::Joined { .. } => "joined",
+            ServerMessage::State { .. } => "state",
+            ServerMessage::Dead { .. } => "dead",
+            ServerMessage::Attached { .. } => "attached",
+            ServerMessage::Announcement { .. } => "announcement",
+            ServerMessage::Kicked { .. } => "kicked",
+            ServerMessage::Shutdown { .. } => "shutdown",
+            ServerMessage::Error { .. } => "error",
+        }
+    }
+
+    /// Serializes the message for a socket and counts it in the metrics.
+    pub fn encode(&self) -> String {
+        let text = serde_json::to_string(self).unwrap();
+        METRICS.record_sent(self.kind(), text.len());
+        text
+    }
+}
+
+/// Why a client is banned and until when
+#[derive(Debug, Serialize, Clone)]
+pub struct BanNotice {
+    /// Human-readable summary
+    pub message: String,
+    pub reason: String,
+    /// RFC 3339 timestamp; absent for permanent bans
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct CellState {
     pub x: f64,
@@ -53,8 +142,22 @@ pub struct CellState {
 pub struct PlayerState {
     pub id: u64,
     pub name: String,
+    /// Name is the player's own registered username
+    pub verified: bool,
+    /// Controlled by an automated client
+    pub bot: bool,
     pub skin: Option<String>,
     pub cells: Vec<CellState>,
+    /// Active power-up effects
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<EffectState>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EffectState {
+    /// `speed`, `shield`, `magnet` or `merge`
+    pub kind: &'static str,
+    pub seconds: f64,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -71,8 +174,49 @@ pub struct VirusState {
     pub radius: f64,
 }
 
+#[derive(Debug, Serialize, Clone)]
+pub struct MothercellState {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PowerUpState {
+    pub x: f64,
+    pub y: f64,
+    /// `speed`, `shield`, `magnet` or `merge`
+    pub kind: &'static str,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct LeaderboardEntry {
     pub name: String,
     pub score: u64,
 }
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RoundState {
+    /// `lobby`, `starting`, `running` or `finished`
+    pub phase: &'static str,
+    pub alive: usize,
+    /// Players needed to start a round
+    pub min_players: usize,
+    /// Seconds until the countdown ends, the results clear, or the zone
+    /// next starts or stops shrinking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seconds: Option<u64>,
+    pub zone: ZoneState,
+    /// Where the zone is shrinking to next
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_zone: Option<ZoneState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winner: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ZoneState {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+}
@@ -0,0 +1,722 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    routing::{delete, get, post},
+    Router,
+};
+use axum_extra::extract::cookie::CookieJar;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::{
+    load_settings, set_settings, settings, settings_path, ADMIN_MAX_SPAWN,
+    MAX_BAN_DAYS, MAX_BAN_REASON_LEN, MAX_BROADCAST_LEN,
+};
+use crate::db::accounts::{Role, User};
+use crate::db::bans::IpNet;
+use crate::db::skins::SkinStatus;
+use crate::game::engine::SharedWorld;
+use crate::game::player::SkinVisibility;
+use crate::names::name_key;
+use crate::protocol::messages::ServerMessage;
+use crate::server::connections::SharedConnections;
+use crate::server::http::{session_user, ApiState, SharedDb};
+
+#[derive(Deserialize)]
+pub struct RejectRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct KickRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MassRequest {
+    pub mass: f64,
+}
+
+#[derive(Deserialize)]
+pub struct SpawnRequest {
+    pub count: usize,
+    /// Optional spawn position (viruses only)
+    #[serde(default)]
+    pub x: Option<f64>,
+    #[serde(default)]
+    pub y: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct BroadcastRequest {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct RoleRequest {
+    pub role: Role,
+}
+
+/// Target a live player, an account, an IP/CIDR, or a combination
+#[derive(Deserialize)]
+pub struct BanRequest {
+    /// Bans the live player's account (if any) and IP
+    #[serde(default)]
+    pub player_id: Option<u64>,
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub ip: Option<String>,
+    pub reason: String,
+    /// Omit for a permanent ban
+    #[serde(default)]
+    pub duration_secs: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    #[serde(default = "default_audit_limit")]
+    pub limit: usize,
+}
+
+fn default_audit_limit() -> usize {
+    100
+}
+
+/// A connected player as seen by admins
+#[derive(Serialize)]
+pub struct LivePlayer {
+    pub id: u64,
+    pub name: String,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub ip: Option<String>,
+    pub mass: f64,
+    pub cells: usize,
+    pub score: u64,
+    pub alive: bool,
+    pub bot: bool,
+}
+
+pub fn admin_routes() -> Router<ApiState> {
+    Router::new()
+        .route("/api/admin/skins", get(skin_queue))
+        .route("/api/admin/skins/:user_id/approve", post(approve_skin))
+        .route("/api/admin/skins/:user_id/reject", post(reject_skin))
+        .route("/api/admin/players", get(list_players))
+        .route("/api/admin/players/:id/kick", post(kick_player))
+        .route("/api/admin/players/:id/mass", post(set_mass))
+        .route("/api/admin/food", post(spawn_food).delete(clear_food))
+        .route("/api/admin/viruses", post(spawn_viruses).delete(clear_viruses))
+        .route("/api/admin/config/reload", post(reload_config))
+        .route("/api/admin/broadcast", post(broadcast))
+        .route("/api/admin/users/:user_id/role", post(set_role))
+        .route("/api/admin/bans", get(list_bans).post(create_ban))
+        .route("/api/admin/bans/:id", delete(lift_ban))
+        .route("/api/admin/audit", get(audit_log))
+}
+
+async fn require_role(
+    db: &SharedDb,
+    jar: &CookieJar,
+    role: Role,
+) -> Result<User, (StatusCode, &'static str)> {
+    match session_user(db, jar).await {
+        Some((user, _)) if user.role >= role => Ok(user),
+        Some(_) if role == Role::Admin => Err((StatusCode::FORBIDDEN, "Admins only")),
+        Some(_) => Err((StatusCode::FORBIDDEN, "Moderators only")),
+        None => Err((StatusCode::UNAUTHORIZED, "Not logged in")),
+    }
+}
+
+/// Writes an action to the audit log (and the server log).
+pub(crate) async fn audit(
+    db: &SharedDb,
+    actor: &User,
+    action: &'static str,
+    target: Option<String>,
+    details: serde_json::Value,
+) {
+    tracing::info!(
+        "admin {} {} {} {}",
+        actor.username,
+        action,
+        target.as_deref().unwrap_or("-"),
+        details
+    );
+    let (actor_id, actor_name) = (actor.id, actor.username.clone());
+    let details = (!details.is_null()).then(|| details.to_string());
+    let result = db
+        .run(move |db| {
+            db.record_audit(actor_id, &actor_name, action, target.as_deref(), details.as_deref())
+        })
+        .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to write audit log: {}", e);
+    }
+}
+
+/// Gives the accounts listed in the settings' `admins` the admin role.
+/// Run at startup and on config reload.
+pub async fn promote_configured_admins(db: &SharedDb) {
+    let admins = settings().admins.clone();
+    let promoted = db
+        .run(move |db| {
+            let mut promoted = Vec::new();
+            for admin in &admins {
+                match db.get_user_by_id(admin.id) {
+                    Some(user) if name_key(&user.username) != name_key(&admin.username) => {
+                        tracing::warn!(
+                            "Configured admin {:?} (id {}) doesn't match account {:?}; not promoting",
+                            admin.username,
+                            admin.id,
+                            user.username
+                        );
+                    }
+                    Some(user) if user.role != Role::Admin => {
+                        if db.set_role(user.id, Role::Admin).is_ok() {
+                            promoted.push(user.username);
+                        }
+                    }
+                    Some(_) => {}
+                    None => tracing::warn!(
+                        "Configured admin {:?} (id {}) has no account",
+                        admin.username,
+                        admin.id
+                    ),
+                }
+            }
+            promoted
+        })
+        .await;
+    for name in promoted {
+        tracing::info!("Promoted {} to admin from settings", name);
+    }
+}
+
+/// Re-reads the settings file and applies it. Leaves the current settings
+/// in place if the file is invalid.
+pub(crate) async fn reload_settings(db: &SharedDb) -> Result<(), String> {
+    set_settings(load_settings(settings_path())?);
+    promote_configured_admins(db).await;
+    Ok(())
+}
+
+async fn skin_queue(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    if let Err(e) = require_role(&db, &jar, Role::Moderator).await {
+        return e.into_response();
+    }
+    match db.run(|db| db.skin_queue()).await {
+        Ok(queue) => Json(queue).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+async fn approve_skin(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let moderator = match require_role(&db, &jar, Role::Moderator).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    match db
+        .run(move |db| db.review_skin(user_id, SkinStatus::Approved, None))
+        .await
+    {
+        Ok(()) => {
+            world.write().await.set_skin_visibility(user_id, SkinVisibility::Public);
+            audit(&db, &moderator, "skin_approve", Some(format!("user:{}", user_id)), json!(null))
+                .await;
+            (StatusCode::OK, "Skin approved").into_response()
+        }
+        Err(e) => (StatusCode::NOT_FOUND, e).into_response(),
+    }
+}
+
+async fn reject_skin(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+    Path(user_id): Path<i64>,
+    Json(req): Json<RejectRequest>,
+) -> impl IntoResponse {
+    let moderator = match require_role(&db, &jar, Role::Moderator).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    let reason = req
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    let stored = reason.clone();
+    match db
+        .run(move |db| db.review_skin(user_id, SkinStatus::Rejected, stored.as_deref()))
+        .await
+    {
+        Ok(()) => {
+            world.write().await.set_skin_visibility(user_id, SkinVisibility::Hidden);
+            audit(
+                &db,
+                &moderator,
+                "skin_reject",
+                Some(format!("user:{}", user_id)),
+                json!({ "reason": reason }),
+            )
+            .await;
+            (StatusCode::OK, "Skin rejected").into_response()
+        }
+        Err(e) => (StatusCode::NOT_FOUND, e).into_response(),
+    }
+}
+
+/// Everyone in the world, by id. `username` is left for the caller.
+pub(crate) async fn live_players(
+    world: &SharedWorld,
+    connections: &SharedConnections,
+) -> Vec<LivePlayer> {
+    let mut players: Vec<LivePlayer> = world
+        .read()
+        .await
+        .players
+        .values()
+        .map(|p| LivePlayer {
+            id: p.id,
+            name: p.name.clone(),
+            user_id: p.user_id,
+            username: None,
+            ip: connections.ip(p.id).map(|ip| ip.to_string()),
+            mass: p.total_mass(),
+            cells: p.cells.len(),
+            score: p.score,
+            alive: p.alive,
+            bot: p.bot,
+        })
+        .collect();
+    players.sort_by_key(|p| p.id);
+    players
+}
+
+async fn list_players(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    State(connections): State<SharedConnections>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    if let Err(e) = require_role(&db, &jar, Role::Admin).await {
+        return e.into_response();
+    }
+
+    let mut players = live_players(&world, &connections).await;
+
+    // Account names can differ from display names
+    let user_ids: Vec<i64> = players.iter().filter_map(|p| p.user_id).collect();
+    let accounts = db
+        .run(move |db| {
+            user_ids
+                .into_iter()
+                .filter_map(|id| db.get_user_by_id(id))
+                .collect::<Vec<_>>()
+        })
+        .await;
+    for player in &mut players {
+        player.username = accounts
+            .iter()
+            .find(|u| Some(u.id) == player.user_id)
+            .map(|u| u.username.clone());
+    }
+    Json(players).into_response()
+}
+
+async fn kick_player(
+    State(db): State<SharedDb>,
+    State(connections): State<SharedConnections>,
+    jar: CookieJar,
+    Path(player_id): Path<u64>,
+    Json(req): Json<KickRequest>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    let reason = req
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .unwrap_or_else(|| "Kicked by an admin".to_string());
+    let ip = connections.ip(player_id);
+    if !connections.kick(player_id, &reason) {
+        return (StatusCode::NOT_FOUND, "Player not connected").into_response();
+    }
+    audit(
+        &db,
+        &admin,
+        "kick",
+        Some(format!("player:{}", player_id)),
+        json!({ "reason": reason, "ip": ip.map(|ip| ip.to_string()) }),
+    )
+    .await;
+    (StatusCode::OK, "Player kicked").into_response()
+}
+
+async fn set_mass(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+    Path(player_id): Path<u64>,
+    Json(req): Json<MassRequest>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    if let Err(e) = world.write().await.set_player_mass(player_id, req.mass) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+    audit(
+        &db,
+        &admin,
+        "set_mass",
+        Some(format!("player:{}", player_id)),
+        json!({ "mass": req.mass }),
+    )
+    .await;
+    (StatusCode::OK, "Mass updated").into_response()
+}
+
+fn check_spawn_count(count: usize) -> Result<(), (StatusCode, String)> {
+    if count == 0 || count > ADMIN_MAX_SPAWN {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Count must be between 1 and {}", ADMIN_MAX_SPAWN),
+        ));
+    }
+    Ok(())
+}
+
+async fn spawn_food(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+    Json(req): Json<SpawnRequest>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    if let Err(e) = check_spawn_count(req.count) {
+        return e.into_response();
+    }
+    let total = {
+        let mut world = world.write().await;
+        world.spawn_food(req.count);
+        world.food.len()
+    };
+    audit(&db, &admin, "spawn_food", None, json!({ "count": req.count })).await;
+    Json(json!({ "food": total })).into_response()
+}
+
+async fn clear_food(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    world.write().await.clear_food();
+    audit(&db, &admin, "clear_food", None, json!(null)).await;
+    Json(json!({ "food": 0 })).into_response()
+}
+
+async fn spawn_viruses(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+    Json(req): Json<SpawnRequest>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    if let Err(e) = check_spawn_count(req.count) {
+        return e.into_response();
+    }
+    let at = req.x.zip(req.y);
+    let total = {
+        let mut world = world.write().await;
+        world.spawn_viruses(req.count, at);
+        world.viruses.len()
+    };
+    audit(
+        &db,
+        &admin,
+        "spawn_viruses",
+        None,
+        json!({ "count": req.count, "x": req.x, "y": req.y }),
+    )
+    .await;
+    Json(json!({ "viruses": total })).into_response()
+}
+
+async fn clear_viruses(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    world.write().await.clear_viruses();
+    audit(&db, &admin, "clear_viruses", None, json!(null)).await;
+    Json(json!({ "viruses": 0 })).into_response()
+}
+
+async fn reload_config(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    if let Err(e) = reload_settings(&db).await {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+    audit(&db, &admin, "reload_config", None, json!({ "path": settings_path() })).await;
+    (StatusCode::OK, "Config reloaded").into_response()
+}
+
+async fn broadcast(
+    State(db): State<SharedDb>,
+    State(connections): State<SharedConnections>,
+    jar: CookieJar,
+    Json(req): Json<BroadcastRequest>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    let message: String = req.message.trim().chars().take(MAX_BROADCAST_LEN).collect();
+    if message.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Message is empty").into_response();
+    }
+    let delivered = connections.broadcast(&ServerMessage::Announcement {
+        message: message.clone(),
+    });
+    audit(&db, &admin, "broadcast", None, json!({ "message": message })).await;
+    Json(json!({ "delivered": delivered })).into_response()
+}
+
+async fn set_role(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Path(user_id): Path<i64>,
+    Json(req): Json<RoleRequest>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    if user_id == admin.id {
+        return (StatusCode::BAD_REQUEST, "You can't change your own role").into_response();
+    }
+    let role = req.role;
+    if let Err(e) = db.run(move |db| db.set_role(user_id, role)).await {
+        return (StatusCode::NOT_FOUND, e).into_response();
+    }
+    audit(
+        &db,
+        &admin,
+        "set_role",
+        Some(format!("user:{}", user_id)),
+        json!({ "role": role.as_str() }),
+    )
+    .await;
+    (StatusCode::OK, "Role updated").into_response()
+}
+
+async fn list_bans(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    if let Err(e) = require_role(&db, &jar, Role::Admin).await {
+        return e.into_response();
+    }
+    match db.run(|db| db.active_bans()).await {
+        Ok(bans) => Json(bans).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+async fn create_ban(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    State(connections): State<SharedConnections>,
+    jar: CookieJar,
+    Json(req): Json<BanRequest>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+
+    let reason = req.reason.trim().to_string();
+    if reason.is_empty() || reason.chars().count() > MAX_BAN_REASON_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Reason must be 1-{} characters", MAX_BAN_REASON_LEN),
+        )
+            .into_response();
+    }
+    let expires_at = match req.duration_secs {
+        Some(secs) if secs <= 0 => {
+            return (StatusCode::BAD_REQUEST, "Duration must be positive").into_response()
+        }
+        // Expiries are compared as RFC 3339 strings, which stop sorting
+        // correctly past year 9999
+        Some(secs) if secs > MAX_BAN_DAYS * 24 * 60 * 60 => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Duration can be at most {} days; omit it for a permanent ban",
+                    MAX_BAN_DAYS
+                ),
+            )
+                .into_response()
+        }
+        Some(secs) => match Duration::try_seconds(secs).and_then(|d| Utc::now().checked_add_signed(d)) {
+            Some(at) => Some(at.to_rfc3339()),
+            None => return (StatusCode::BAD_REQUEST, "Duration too long").into_response(),
+        },
+        None => None,
+    };
+
+    let mut user_id = req.user_id;
+    let mut ip = match req.ip.as_deref().map(str::parse::<IpNet>).transpose() {
+        Ok(ip) => ip,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if let Some(player_id) = req.player_id {
+        let Some(player_ip) = connections.ip(player_id) else {
+            return (StatusCode::NOT_FOUND, "Player not connected").into_response();
+        };
+        ip = ip.or(player_ip.to_string().parse().ok());
+        user_id = user_id.or(world
+            .read()
+            .await
+            .players
+            .get(&player_id)
+            .and_then(|p| p.user_id));
+    }
+    if let Some(username) = req.username {
+        let key = name_key(&username);
+        match db.run(move |db| db.find_user_by_name_key(&key)).await {
+            Some(user) => user_id = Some(user.id),
+            None => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        }
+    }
+    if user_id == Some(admin.id) {
+        return (StatusCode::BAD_REQUEST, "You can't ban yourself").into_response();
+    }
+
+    let (stored_reason, stored_expiry, created_by) =
+        (reason.clone(), expires_at.clone(), admin.username.clone());
+    let ban = match db
+        .run(move |db| {
+            db.create_ban(
+                user_id,
+                ip,
+                &stored_reason,
+                stored_expiry.as_deref(),
+                &created_by,
+            )
+        })
+        .await
+    {
+        Ok(ban) => ban,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    // Disconnect everyone the ban covers
+    let mut targets = ip
+        .map(|net| connections.players_where(|addr| net.contains(addr)))
+        .unwrap_or_default();
+    if let Some(uid) = user_id {
+        let world = world.read().await;
+        targets.extend(
+            world
+                .players
+                .values()
+                .filter(|p| p.user_id == Some(uid))
+                .map(|p| p.id),
+        );
+    }
+    targets.sort_unstable();
+    targets.dedup();
+    let message = ban.notice().message;
+    let kicked = targets
+        .into_iter()
+        .filter(|id| connections.kick(*id, &message))
+        .count();
+
+    audit(
+        &db,
+        &admin,
+        "ban",
+        Some(format!("ban:{}", ban.id)),
+        json!({
+            "user_id": user_id,
+            "ip": ban.ip,
+            "reason": reason,
+            "expires_at": expires_at,
+            "kicked": kicked,
+        }),
+    )
+    .await;
+    Json(json!({ "ban": ban, "kicked": kicked })).into_response()
+}
+
+async fn lift_ban(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Path(ban_id): Path<i64>,
+) -> impl IntoResponse {
+    let admin = match require_role(&db, &jar, Role::Admin).await {
+        Ok(u) => u,
+        Err(e) => return e.into_response(),
+    };
+    if let Err(e) = db.run(move |db| db.lift_ban(ban_id)).await {
+        return (StatusCode::NOT_FOUND, e).into_response();
+    }
+    audit(&db, &admin, "unban", Some(format!("ban:{}", ban_id)), json!(null)).await;
+    (StatusCode::OK, "Ban lifted").into_response()
+}
+
+async fn audit_log(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    if let Err(e) = require_role(&db, &jar, Role::Admin).await {
+        return e.into_response();
+    }
+    let limit = query.limit.clamp(1, 1000);
+    match db.run(move |db| db.audit_log(limit)).await {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
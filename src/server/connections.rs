@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use axum::extract::ws::Message;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::config::OUTBOUND_QUEUE_LIMIT;
+use crate::metrics::{inc, METRICS};
+use crate::protocol::messages::ServerMessage;
+
+/// Sending half of a socket's outbound queue. Tracks how many messages
+/// are waiting so state updates to a slow client can be skipped instead
+/// of piling up.
+#[derive(Clone)]
+pub struct Outbox {
+    tx: UnboundedSender<Message>,
+    depth: Arc<AtomicUsize>,
+}
+
+/// Receiving half of an [`Outbox`], drained by the socket's writer task
+pub struct OutboxReceiver {
+    rx: UnboundedReceiver<Message>,
+    depth: Arc<AtomicUsize>,
+}
+
+impl Outbox {
+    pub fn channel() -> (Outbox, OutboxReceiver) {
+        let (tx, rx) = unbounded_channel();
+        let depth = Arc::new(AtomicUsize::new(0));
+        (
+            Outbox {
+                tx,
+                depth: depth.clone(),
+            },
+            OutboxReceiver { rx, depth },
+        )
+    }
+
+    fn push(&self, msg: Message) -> bool {
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(msg).is_ok()
+    }
+
+    /// Queues a message. Returns false once the socket is gone.
+    pub fn send(&self, msg: &ServerMessage) -> bool {
+        self.push(Message::Text(msg.encode()))
+    }
+
+    /// Like [`send`](Self::send), but skips the message if the client is
+    /// already `OUTBOUND_QUEUE_LIMIT` messages behind.
+    pub fn send_droppable(&self, msg: &ServerMessage) -> bool {
+        if self.depth.load(Ordering::Relaxed) >= OUTBOUND_QUEUE_LIMIT {
+            inc(&METRICS.queue_drops);
+            return !self.tx.is_closed();
+        }
+        self.send(msg)
+    }
+
+    /// Queues a close frame; the writer task stops after sending it.
+    pub fn close(&self) {
+        self.push(Message::Close(None));
+    }
+}
+
+impl OutboxReceiver {
+    pub async fn recv(&mut self) -> Option<Message> {
+        let msg = self.rx.recv().await;
+        if msg.is_some() {
+            self.depth.fetch_sub(1, Ordering::Relaxed);
+        }
+        msg
+    }
+}
+
+struct Connection {
+    ip: IpAddr,
+    outbox: Outbox,
+}
+
+/// Live WebSocket connections by player id. Lets the HTTP side look up a
+/// player's IP and push messages to, or close, their socket.
+#[derive(Default)]
+pub struct Connections {
+    conns: Mutex<HashMap<u64, Connection>>,
+    /// Set once shutdown starts; no new players are let in
+    draining: AtomicBool,
+}
+
+pub type SharedConnections = Arc<Connections>;
+
+impl Connections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, player_id: u64, ip: IpAddr, outbox: Outbox) {
+        self.conns
+            .lock()
+            .unwrap()
+            .insert(player_id, Connection { ip, outbox });
+    }
+
+    pub fn unregister(&self, player_id: u64) {
+        self.conns.lock().unwrap().remove(&player_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.conns.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops new players from joining
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    pub fn ip(&self, player_id: u64) -> Option<IpAddr> {
+        self.conns.lock().unwrap().get(&player_id).map(|c| c.ip)
+    }
+
+    /// Players whose connection address satisfies `pred`
+    pub fn players_where(&self, pred: impl Fn(IpAddr) -> bool) -> Vec<u64> {
+        self.conns
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, c)| pred(c.ip))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Sends a message to every connected player. Returns how many got it.
+    pub fn broadcast(&self, msg: &ServerMessage) -> usize {
+        self.conns
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|c| c.outbox.send(msg))
+            .count()
+    }
+
+    /// Tells the player why and closes their socket. Returns false if the
+    /// player isn't connected.
+    pub fn kick(&self, player_id: u64, reason: &str) -> bool {
+        let conns = self.conns.lock().unwrap();
+        let Some(conn) = conns.get(&player_id) else {
+            return false;
+        };
+        conn.outbox.send(&ServerMessage::Kicked {
+            reason: reason.to_string(),
+        });
+        conn.outbox.close();
+        true
+    }
+
+    /// Kicks everyone. Returns how many connections were told.
+    pub fn kick_all(&self, reason: &str) -> usize {
+        let ids: Vec<u64> = self.conns.lock().unwrap().keys().copied().collect();
+        ids.into_iter().filter(|id| self.kick(*id, reason)).count()
+    }
+}
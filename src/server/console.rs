@@ -0,0 +1,288 @@
+use serde_json::json;
+
+use crate::config::{ADMIN_MAX_SPAWN, MAX_BROADCAST_LEN, MAX_STEP_TICKS};
+use crate::db::accounts::{Role, User};
+use crate::db::SharedDb;
+use crate::game::engine::{SharedWorld, LOOP_CONTROL};
+use crate::protocol::messages::ServerMessage;
+use crate::server::admin::{audit, live_players, reload_settings};
+use crate::server::connections::SharedConnections;
+
+const HELP: &str = "\
+players                 list connected players
+kick <id> [reason]      disconnect a player
+mass <id> <value>       set a player's total mass
+spawn virus <x> <y>     spawn a virus at a position
+food <count>|clear      spawn food, or remove all of it
+say <message>           announce to every player
+pause | resume          stop or restart the tick loop
+step [n]                run n ticks while paused (default 1)
+reload                  re-read the settings file
+help                    show this list";
+
+/// Operator commands against the live server, from stdin or the console
+/// socket. Actions are audited under the `console` actor.
+#[derive(Clone)]
+pub struct Console {
+    pub world: SharedWorld,
+    pub db: SharedDb,
+    pub connections: SharedConnections,
+}
+
+impl Console {
+    fn operator() -> User {
+        User {
+            id: 0,
+            username: "console".into(),
+            role: Role::Admin,
+        }
+    }
+
+    /// Runs one command line and returns its output.
+    pub async fn execute(&self, line: &str) -> String {
+        let mut args = line.split_whitespace();
+        let Some(command) = args.next() else {
+            return String::new();
+        };
+        let args: Vec<&str> = args.collect();
+        match self.run(command, &args).await {
+            Ok(out) => out,
+            Err(e) => format!("error: {}", e),
+        }
+    }
+
+    async fn run(&self, command: &str, args: &[&str]) -> Result<String, String> {
+        match (command, args) {
+            ("help", _) => Ok(HELP.to_string()),
+            ("players", []) => Ok(self.players().await),
+            ("kick", [id, reason @ ..]) => {
+                let id = parse::<u64>(id, "player id")?;
+                let reason = if reason.is_empty() {
+                    "Kicked by the server operator".to_string()
+                } else {
+                    reason.join(" ")
+                };
+                if !self.connections.kick(id, &reason) {
+                    return Err("player not connected".into());
+                }
+                self.audit("kick", Some(format!("player:{}", id)), json!({ "reason": reason }))
+                    .await;
+                Ok(format!("kicked {}", id))
+            }
+            ("mass", [id, mass]) => {
+                let id = parse::<u64>(id, "player id")?;
+                let mass = parse_finite(mass, "mass")?;
+                self.world.write().await.set_player_mass(id, mass)?;
+                self.audit("set_mass", Some(format!("player:{}", id)), json!({ "mass": mass }))
+                    .await;
+                Ok(format!("player {} now has mass {}", id, mass))
+            }
+            ("spawn", ["virus", x, y]) => {
+                let (x, y) = (parse_finite(x, "x")?, parse_finite(y, "y")?);
+                self.world.write().await.spawn_viruses(1, Some((x, y)));
+                self.audit("spawn_viruses", None, json!({ "count": 1, "x": x, "y": y }))
+                    .await;
+                Ok(format!("spawned a virus at {} {}", x, y))
+            }
+            ("food", ["clear"]) => {
+                self.world.write().await.clear_food();
+                self.audit("clear_food", None, json!(null)).await;
+                Ok("cleared food".into())
+            }
+            ("food", [count]) => {
+                let count = parse::<usize>(count, "count")?;
+                if count == 0 || count > ADMIN_MAX_SPAWN {
+                    return Err(format!("count must be between 1 and {}", ADMIN_MAX_SPAWN));
+                }
+                let total = {
+                    let mut world = self.world.write().await;
+                    world.spawn_food(count);
+                    world.food.len()
+                };
+                self.audit("spawn_food", None, json!({ "count": count })).await;
+                Ok(format!("spawned {} food ({} total)", count, total))
+            }
+            ("say", words) if !words.is_empty() => {
+                let message: String = words.join(" ").chars().take(MAX_BROADCAST_LEN).collect();
+                let delivered = self.connections.broadcast(&ServerMessage::Announcement {
+                    message: message.clone(),
+                });
+                self.audit("broadcast", None, json!({ "message": message })).await;
+                Ok(format!("sent to {} players", delivered))
+            }
+            ("pause", []) => {
+                LOOP_CONTROL.pause();
+                self.audit("pause", None, json!(null)).await;
+                Ok("paused".into())
+            }
+            ("resume", []) => {
+                LOOP_CONTROL.resume();
+                self.audit("resume", None, json!(null)).await;
+                Ok("resumed".into())
+            }
+            ("step", n) if n.len() <= 1 => {
+                if !LOOP_CONTROL.is_paused() {
+                    return Err("not paused".into());
+                }
+                let n = match n {
+                    [n] => parse::<u32>(n, "tick count")?,
+                    _ => 1,
+                };
+                if n == 0 || n > MAX_STEP_TICKS {
+                    return Err(format!("tick count must be between 1 and {}", MAX_STEP_TICKS));
+                }
+                LOOP_CONTROL.step(n);
+                Ok(format!("stepping {} ticks", n))
+            }
+            ("reload", []) => {
+                reload_settings(&self.db).await?;
+                self.audit("reload_config", None, json!(null)).await;
+                Ok("settings reloaded".into())
+            }
+            _ => Err(format!("bad command: {} (try help)", command)),
+        }
+    }
+
+    async fn players(&self) -> String {
+        let players = live_players(&self.world, &self.connections).await;
+        if players.is_empty() {
+            return "no players".into();
+        }
+        let mut out = format!(
+            "{:>5}  {:<20} {:>7} {:<39} {:>9} {:>5}",
+            "id", "name", "account", "ip", "mass", "cells"
+        );
+        for p in players {
+            out.push_str(&format!(
+                "\n{:>5}  {:<20} {:>7} {:<39} {:>9.0} {:>5}{}{}",
+                p.id,
+                p.name,
+                p.user_id.map(|id| id.to_string()).unwrap_or_else(|| "-".into()),
+                p.ip.unwrap_or_else(|| "-".into()),
+                p.mass,
+                p.cells,
+                if p.alive { "" } else { " (dead)" },
+                if p.bot { " (bot)" } else { "" },
+            ));
+        }
+        out
+    }
+
+    async fn audit(&self, action: &'static str, target: Option<String>, details: serde_json::Value) {
+        audit(&self.db, &Self::operator(), action, target, details).await;
+    }
+
+    /// Reads commands line by line until `input` ends, writing results to
+    /// `output`.
+    #[cfg(unix)]
+    async fn serve<R, W>(&self, input: R, mut output: W)
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let mut lines = BufReader::new(input).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let out = self.execute(&line).await;
+            if out.is_empty() {
+                continue;
+            }
+            if output.write_all(format!("{}\n", out).as_bytes()).await.is_err() {
+                break;
+            }
+            let _ = output.flush().await;
+        }
+    }
+
+    /// Serves the console on the process's stdin/stdout. Stdin is read on
+    /// its own thread: a read pending in Tokio's blocking pool would hold
+    /// up runtime shutdown until the operator pressed Enter.
+    pub async fn run_stdin(self) {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        while let Some(line) = rx.recv().await {
+            let out = self.execute(&line).await;
+            if !out.is_empty() {
+                println!("{}", out);
+            }
+        }
+    }
+
+    /// Serves the console on a Unix socket at `path`, readable only by the
+    /// server's user. Try it with `socat - UNIX-CONNECT:<path>`.
+    #[cfg(unix)]
+    pub async fn run_socket(self, path: String) {
+        let listener = match bind_private_socket(&path) {
+            Ok(l) => l,
+            Err(e) => {
+                tracing::error!("Console socket {}: {}", path, e);
+                return;
+            }
+        };
+        println!("🖥️  Console listening on {}", path);
+
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let console = self.clone();
+            tokio::spawn(async move {
+                let (read, write) = stream.into_split();
+                console.serve(read, write).await;
+            });
+        }
+    }
+}
+
+/// Binds a Unix socket at `path` that only the server's user can connect to.
+/// The socket is created inside a fresh 0700 directory and made 0600 before
+/// it is moved into place, so it is never reachable with looser permissions.
+/// Anything other than a socket already at `path` is left alone.
+#[cfg(unix)]
+fn bind_private_socket(path: &str) -> std::io::Result<tokio::net::UnixListener> {
+    use std::fs::{self, DirBuilder, Permissions};
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+    let private_dir = format!("{}.{}.tmp", path, std::process::id());
+    let _ = fs::remove_dir_all(&private_dir);
+    DirBuilder::new().mode(0o700).create(&private_dir)?;
+    let staged = format!("{}/console.sock", private_dir);
+
+    let bound = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        fs::set_permissions(&staged, Permissions::from_mode(0o600))?;
+        // Replaces a socket a previous run may have left behind, but
+        // nothing else a misconfigured path might point at
+        match fs::symlink_metadata(path) {
+            Ok(meta) if !meta.file_type().is_socket() => {
+                return Err(Error::new(ErrorKind::AlreadyExists, "exists and is not a socket"));
+            }
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_dir_all(&private_dir);
+    bound
+}
+
+fn parse<T: std::str::FromStr>(s: &str, what: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid {}: {}", what, s))
+}
+
+/// Like [`parse`], but also refuses `NaN` and `inf`, which `f64` accepts
+fn parse_finite(s: &str, what: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("invalid {}: {}", what, s))
+}
@@ -0,0 +1,121 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    routing::get,
+    Router,
+};
+use serde::Serialize;
+use tokio::time::{timeout, Duration};
+
+use crate::config::{DEFAULT_ROOM, READY_DB_TIMEOUT_MS, READY_MAX_TICK_AGE_MS, TICK_RATE};
+use crate::game::engine::{SharedWorld, LOOP_CONTROL, LOOP_STATUS};
+use crate::server::connections::SharedConnections;
+use crate::server::http::{ApiState, SharedDb};
+
+#[derive(Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// What's wrong, when not ready
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct RoomStatus {
+    pub name: String,
+    pub players: usize,
+    pub alive: usize,
+    pub bots: usize,
+}
+
+#[derive(Serialize)]
+pub struct ServerStatus {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    /// Measured ticks per second, and the target
+    pub tps: f64,
+    pub target_tps: u64,
+    /// Tick loop paused from the operator console
+    pub paused: bool,
+    pub connections: usize,
+    pub players: usize,
+    pub rooms: Vec<RoomStatus>,
+}
+
+pub fn health_routes() -> Router<ApiState> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/api/status", get(status))
+}
+
+/// The process is up and serving requests
+async fn healthz() -> impl IntoResponse {
+    "ok"
+}
+
+/// Ready for traffic: the database answers, the game loop is ticking and
+/// the server isn't shutting down.
+async fn readyz(
+    State(db): State<SharedDb>,
+    State(connections): State<SharedConnections>,
+) -> impl IntoResponse {
+    let mut problems = Vec::new();
+
+    let ping = timeout(
+        Duration::from_millis(READY_DB_TIMEOUT_MS),
+        db.run(|db| db.ping()),
+    )
+    .await;
+    match ping {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => problems.push(format!("database: {}", e)),
+        Err(_) => problems.push("database: timed out".into()),
+    }
+
+    if let Some(problem) = LOOP_STATUS.problem(Duration::from_millis(READY_MAX_TICK_AGE_MS)) {
+        problems.push(problem);
+    }
+    if connections.is_draining() {
+        problems.push("shutting down".into());
+    }
+
+    let status = if problems.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(Readiness {
+            ready: problems.is_empty(),
+            problems,
+        }),
+    )
+}
+
+async fn status(
+    State(world): State<SharedWorld>,
+    State(connections): State<SharedConnections>,
+) -> impl IntoResponse {
+    let room = {
+        let world = world.read().await;
+        RoomStatus {
+            name: DEFAULT_ROOM.to_string(),
+            players: world.players.len(),
+            alive: world.players.values().filter(|p| p.alive).count(),
+            bots: world.players.values().filter(|p| p.bot).count(),
+        }
+    };
+    Json(ServerStatus {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: LOOP_STATUS.uptime().as_secs(),
+        tps: (LOOP_STATUS.tps() * 10.0).round() / 10.0,
+        target_tps: TICK_RATE,
+        paused: LOOP_CONTROL.is_paused(),
+        connections: connections.len(),
+        players: room.players,
+        rooms: vec![room],
+    })
+}
@@ -1,17 +1,75 @@
 use axum::{
-    extract::{Multipart, Path, State},
-    http::{header, StatusCode},
+    extract::{ConnectInfo, FromRef, Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Json},
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar};
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use crate::db::Database;
-use crate::config::MAX_SKIN_SIZE;
+use std::time::Duration;
+use crate::db::accounts::{Role, User};
+use crate::db::bans::Ban;
+use crate::db::friends::{Friend, FriendStatus};
+use crate::db::skins::SkinStatus;
+use crate::db::stats::Stats;
+use crate::db::tokens::{ApiToken, TokenScope};
+pub use crate::db::SharedDb;
+use crate::game::engine::SharedWorld;
+use crate::game::player::SkinVisibility;
+use crate::config::{settings, DEFAULT_ROOM, MAX_API_TOKENS, MAX_API_TOKEN_NAME_LEN, MAX_SKIN_SIZE};
+use crate::metrics::{inc, Exposition, METRICS};
+use crate::names;
+use crate::protocol::messages::BanNotice;
+use crate::server::admin;
+use crate::server::health;
+use crate::server::connections::SharedConnections;
+use crate::server::presence::SharedPresence;
+use crate::server::ratelimit::RateLimiter;
+use crate::server::skin_image;
 
-pub type SharedDb = Arc<Database>;
+pub type SharedLimiter = Arc<RateLimiter>;
+
+#[derive(Clone)]
+pub struct ApiState {
+    pub db: SharedDb,
+    pub limiter: SharedLimiter,
+    pub world: SharedWorld,
+    pub presence: SharedPresence,
+    pub connections: SharedConnections,
+}
+
+impl FromRef<ApiState> for SharedDb {
+    fn from_ref(state: &ApiState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<ApiState> for SharedLimiter {
+    fn from_ref(state: &ApiState) -> Self {
+        state.limiter.clone()
+    }
+}
+
+impl FromRef<ApiState> for SharedWorld {
+    fn from_ref(state: &ApiState) -> Self {
+        state.world.clone()
+    }
+}
+
+impl FromRef<ApiState> for SharedConnections {
+    fn from_ref(state: &ApiState) -> Self {
+        state.connections.clone()
+    }
+}
+
+impl FromRef<ApiState> for SharedPresence {
+    fn from_ref(state: &ApiState) -> Self {
+        state.presence.clone()
+    }
+}
 
 #[derive(Deserialize)]
 pub struct AuthRequest {
@@ -27,46 +85,275 @@ pub struct AuthResponse {
     pub username: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_id: Option<i64>,
+    /// Set when the client or account is banned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban: Option<BanNotice>,
 }
 
-pub fn api_routes(db: SharedDb) -> Router {
+#[derive(Deserialize)]
+pub struct ReportSkinRequest {
+    /// In-game player id whose skin is being reported
+    pub player_id: u64,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct RoundsQuery {
+    #[serde(default = "default_rounds_limit")]
+    pub limit: usize,
+}
+
+fn default_rounds_limit() -> usize {
+    20
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct FriendRequest {
+    pub username: String,
+}
+
+/// A friends-list entry. Presence is only shown for accepted friends.
+#[derive(Serialize)]
+pub struct FriendEntry {
+    #[serde(flatten)]
+    pub friend: Friend,
+    pub online: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+}
+
+/// Returned once, when a token is created
+#[derive(Serialize)]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    pub info: ApiToken,
+    pub token: String,
+}
+
+#[derive(Serialize)]
+pub struct StatsResponse {
+    pub user_id: i64,
+    pub username: String,
+    pub stats: Option<Stats>,
+}
+
+pub fn api_routes(state: ApiState) -> Router {
     Router::new()
         .route("/api/register", post(register))
         .route("/api/login", post(login))
         .route("/api/logout", post(logout))
-        .route("/api/me", get(me))
+        .route("/api/me", get(me).delete(delete_me))
+        .route("/api/me/password", post(change_password))
+        .route("/api/me/export", get(export_me))
+        .route("/api/me/stats", get(my_stats))
+        .route("/api/tokens", get(list_tokens).post(create_token))
+        .route("/api/tokens/:id", delete(revoke_token))
+        .route("/api/friends", get(list_friends).post(add_friend))
+        .route("/api/friends/:id/accept", post(accept_friend))
+        .route("/api/friends/:id", delete(remove_friend))
         .route("/api/skin", post(upload_skin))
-        .route("/api/skin/{id}", get(get_skin))
-        .with_state(db)
+        .route("/api/skin/report", post(report_skin))
+        .route("/api/skin/:id", get(get_skin))
+        .route("/api/rounds", get(recent_rounds))
+        .route("/metrics", get(metrics))
+        .merge(admin::admin_routes())
+        .merge(health::health_routes())
+        .with_state(state)
 }
 
-async fn register(
-    State(db): State<SharedDb>,
-    Json(req): Json<AuthRequest>,
-) -> impl IntoResponse {
-    match db.register(&req.username, &req.password) {
-        Ok(user) => Json(AuthResponse {
-            ok: true,
-            message: "Account created".into(),
-            username: Some(user.username),
-            user_id: Some(user.id),
+/// Resolves the session cookie to a user and its token.
+pub(crate) async fn session_user(db: &SharedDb, jar: &CookieJar) -> Option<(User, String)> {
+    let token = jar.get("session")?.value().to_string();
+    let lookup = token.clone();
+    let user = db.run(move |db| db.validate_session(&lookup)).await?;
+    Some((user, token))
+}
+
+/// Resolves the caller from an `Authorization: Bearer` API token, which must
+/// carry `scope`, or else from the session cookie.
+pub(crate) async fn api_user(
+    db: &SharedDb,
+    jar: &CookieJar,
+    headers: &HeaderMap,
+    scope: TokenScope,
+) -> Result<User, (StatusCode, &'static str)> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let Some(secret) = bearer else {
+        return match session_user(db, jar).await {
+            Some((user, _)) => Ok(user),
+            None => Err((StatusCode::UNAUTHORIZED, "Not logged in")),
+        };
+    };
+
+    let secret = secret.trim().to_string();
+    match db.run(move |db| db.validate_token(&secret)).await {
+        Some((user, scopes)) if scopes.contains(&scope) => Ok(user),
+        Some(_) => Err((StatusCode::FORBIDDEN, "Token lacks the required scope")),
+        None => Err((StatusCode::UNAUTHORIZED, "Invalid API token")),
+    }
+}
+
+fn not_logged_in() -> (StatusCode, Json<AuthResponse>) {
+    auth_error(StatusCode::UNAUTHORIZED, "Not logged in".into())
+}
+
+/// 403 for a banned client or account, with the ban's details
+fn banned(ban: &Ban) -> axum::response::Response {
+    let notice = ban.notice();
+    (
+        StatusCode::FORBIDDEN,
+        Json(AuthResponse {
+            ok: false,
+            message: notice.message.clone(),
+            username: None,
+            user_id: None,
+            ban: Some(notice),
         }),
-        Err(e) => Json(AuthResponse {
+    )
+        .into_response()
+}
+
+/// Refuses an auth request whose ban lookup failed; it mustn't fail open
+fn ban_check_failed(e: String) -> axum::response::Response {
+    tracing::error!("Ban lookup failed: {}", e);
+    auth_error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Server error, please try again later".into(),
+    )
+    .into_response()
+}
+
+fn auth_error(status: StatusCode, message: String) -> (StatusCode, Json<AuthResponse>) {
+    (
+        status,
+        Json(AuthResponse {
             ok: false,
-            message: e,
+            message,
             username: None,
             user_id: None,
+            ban: None,
         }),
+    )
+}
+
+fn too_many_attempts(retry_after: Duration) -> axum::response::Response {
+    let secs = retry_after.as_millis().div_ceil(1000).max(1);
+    (
+        [(header::RETRY_AFTER, secs.to_string())],
+        auth_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Too many attempts, try again in {} seconds", secs),
+        ),
+    )
+        .into_response()
+}
+
+async fn register(
+    State(db): State<SharedDb>,
+    State(limiter): State<SharedLimiter>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<AuthRequest>,
+) -> impl IntoResponse {
+    // Fold look-alike spellings so they share one rate-limit bucket
+    let limit_key = names::name_key(&req.username);
+    if let Err(retry_after) = limiter.check(addr.ip(), &limit_key) {
+        return too_many_attempts(retry_after);
+    }
+    let ip = addr.ip();
+    match db.run(move |db| db.find_ban(None, Some(ip))).await {
+        Ok(None) => {}
+        Ok(Some(ban)) => return banned(&ban),
+        Err(e) => return ban_check_failed(e),
+    }
+
+    // Argon2 hashing is CPU-heavy; Database::run keeps it off the async workers
+    let username = req.username.clone();
+    let result = db.run(move |db| db.register(&username, &req.password)).await;
+
+    // Registration errors are validation failures (name taken, bad password),
+    // not credential guesses, so they only count against the attempt window
+    match result {
+        Ok(user) => {
+            Json(AuthResponse {
+                ok: true,
+                message: "Account created".into(),
+                username: Some(user.username),
+                user_id: Some(user.id),
+                ban: None,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            Json(AuthResponse {
+                ok: false,
+                message: e,
+                username: None,
+                user_id: None,
+                ban: None,
+            })
+            .into_response()
+        }
     }
 }
 
 async fn login(
     State(db): State<SharedDb>,
+    State(limiter): State<SharedLimiter>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     jar: CookieJar,
     Json(req): Json<AuthRequest>,
 ) -> impl IntoResponse {
-    match db.login(&req.username, &req.password) {
-        Ok((user, token)) => {
+    // Fold look-alike spellings so they share one rate-limit bucket
+    let limit_key = names::name_key(&req.username);
+    if let Err(retry_after) = limiter.check(addr.ip(), &limit_key) {
+        return too_many_attempts(retry_after);
+    }
+    let ip = addr.ip();
+    match db.run(move |db| db.find_ban(None, Some(ip))).await {
+        Ok(None) => {}
+        Ok(Some(ban)) => return banned(&ban),
+        Err(e) => return ban_check_failed(e),
+    }
+
+    let username = req.username.clone();
+    // Account bans are only revealed once the password checks out
+    let result = db
+        .run(move |db| {
+            db.login(&username, &req.password).map(|(user, token)| {
+                let ban = db.find_ban(Some(user.id), None);
+                if !matches!(ban, Ok(None)) {
+                    db.logout(&token);
+                }
+                (user, token, ban)
+            })
+        })
+        .await;
+
+    match result {
+        Ok((_, _, Err(e))) => ban_check_failed(e),
+        Ok((_, _, Ok(Some(ban)))) => {
+            limiter.record_success(addr.ip(), &limit_key);
+            inc(&METRICS.logins_banned);
+            banned(&ban)
+        }
+        Ok((user, token, Ok(None))) => {
+            limiter.record_success(addr.ip(), &limit_key);
+            inc(&METRICS.logins_succeeded);
             let cookie = Cookie::build(("session", token))
                 .path("/")
                 .http_only(false) // JS needs to read it for WS
@@ -79,18 +366,26 @@ async fn login(
                     message: "Logged in".into(),
                     username: Some(user.username),
                     user_id: Some(user.id),
+                    ban: None,
                 }),
             )
+                .into_response()
+        }
+        Err(e) => {
+            limiter.record_failure(addr.ip(), &limit_key);
+            inc(&METRICS.logins_failed);
+            (
+                jar,
+                Json(AuthResponse {
+                    ok: false,
+                    message: e,
+                    username: None,
+                    user_id: None,
+                    ban: None,
+                }),
+            )
+                .into_response()
         }
-        Err(e) => (
-            jar,
-            Json(AuthResponse {
-                ok: false,
-                message: e,
-                username: None,
-                user_id: None,
-            }),
-        ),
     }
 }
 
@@ -99,7 +394,8 @@ async fn logout(
     jar: CookieJar,
 ) -> impl IntoResponse {
     if let Some(cookie) = jar.get("session") {
-        db.logout(cookie.value());
+        let token = cookie.value().to_string();
+        db.run(move |db| db.logout(&token)).await;
     }
     let removal = Cookie::build(("session", ""))
         .path("/")
@@ -112,6 +408,7 @@ async fn logout(
             message: "Logged out".into(),
             username: None,
             user_id: None,
+            ban: None,
         }),
     )
 }
@@ -119,56 +416,272 @@ async fn logout(
 async fn me(
     State(db): State<SharedDb>,
     jar: CookieJar,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    if let Some(cookie) = jar.get("session") {
-        if let Some(user) = db.validate_session(cookie.value()) {
+    let message = match api_user(&db, &jar, &headers, TokenScope::ReadStats).await {
+        Ok(user) => {
             return Json(AuthResponse {
                 ok: true,
                 message: "Authenticated".into(),
                 username: Some(user.username),
                 user_id: Some(user.id),
-            });
+                ban: None,
+            })
         }
-    }
+        Err((_, message)) => message,
+    };
     Json(AuthResponse {
         ok: false,
-        message: "Not logged in".into(),
+        message: message.into(),
         username: None,
         user_id: None,
+        ban: None,
     })
 }
 
-async fn upload_skin(
+async fn my_stats(
     State(db): State<SharedDb>,
     jar: CookieJar,
-    mut multipart: Multipart,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let user = match api_user(&db, &jar, &headers, TokenScope::ReadStats).await {
+        Ok(user) => user,
+        Err(e) => return e.into_response(),
+    };
+
+    let user_id = user.id;
+    let stats = db.run(move |db| db.get_stats(user_id)).await;
+    Json(StatsResponse {
+        user_id,
+        username: user.username,
+        stats,
+    })
+    .into_response()
+}
+
+/// Latest battle royale results, newest first
+async fn recent_rounds(
+    State(db): State<SharedDb>,
+    Query(query): Query<RoundsQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.clamp(1, 100);
+    match db.run(move |db| db.recent_rounds(limit)).await {
+        Ok(rounds) => Json(rounds).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+async fn list_tokens(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    match db.run(move |db| db.list_tokens(user.id)).await {
+        Ok(tokens) => Json(tokens).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Tokens can only be managed with a real login session, never with
+/// another token.
+async fn create_token(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Json(req): Json<CreateTokenRequest>,
 ) -> impl IntoResponse {
-    let user = match jar.get("session").and_then(|c| db.validate_session(c.value())) {
-        Some(u) => u,
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
         None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
     };
 
-    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
-        let content_type = field
-            .content_type()
-            .unwrap_or("application/octet-stream")
-            .to_string();
+    let name = req.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_API_TOKEN_NAME_LEN {
+        let msg = format!("Token name must be 1-{} characters", MAX_API_TOKEN_NAME_LEN);
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+    let mut scopes = req.scopes;
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Pick at least one scope").into_response();
+    }
 
-        if !content_type.starts_with("image/") {
-            return (StatusCode::BAD_REQUEST, "Only image files allowed").into_response();
+    let result = db
+        .run(move |db| {
+            if db.list_tokens(user.id)?.len() >= MAX_API_TOKENS {
+                return Err(format!("You can have at most {} tokens", MAX_API_TOKENS));
+            }
+            db.create_token(user.id, &name, &scopes)
+        })
+        .await;
+    match result {
+        Ok((info, token)) => {
+            tracing::info!("User {} created API token {:?}", user.username, info.name);
+            (StatusCode::CREATED, Json(CreatedToken { info, token })).into_response()
         }
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
 
-        let data = match field.bytes().await {
-            Ok(d) => d,
-            Err(_) => return (StatusCode::BAD_REQUEST, "Failed to read file").into_response(),
-        };
+async fn revoke_token(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Path(token_id): Path<i64>,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    match db.run(move |db| db.revoke_token(user.id, token_id)).await {
+        Ok(()) => (StatusCode::OK, "Token revoked").into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e).into_response(),
+    }
+}
 
-        if data.len() > MAX_SKIN_SIZE {
-            return (StatusCode::BAD_REQUEST, "File too large (max 256KB)").into_response();
+async fn change_password(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Json(req): Json<ChangePasswordRequest>,
+) -> impl IntoResponse {
+    let (user, token) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return not_logged_in(),
+    };
+
+    let (user_id, username) = (user.id, user.username);
+    let result = db
+        .run(move |db| db.change_password(user_id, &req.old_password, &req.new_password, &token))
+        .await;
+
+    match result {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(AuthResponse {
+                ok: true,
+                message: "Password changed".into(),
+                username: Some(username),
+                user_id: Some(user_id),
+                ban: None,
+            }),
+        ),
+        Err(e) => auth_error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+async fn delete_me(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    State(presence): State<SharedPresence>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (jar, not_logged_in()),
+    };
+
+    let user_id = user.id;
+    match db.run(move |db| db.delete_account(user_id)).await {
+        Ok(()) => {
+            // Players still in the game carry on as guests
+            for player_id in world.write().await.detach_account(user_id) {
+                presence.leave(user_id, player_id);
+            }
+            let removal = Cookie::build(("session", ""))
+                .path("/")
+                .max_age(time::Duration::seconds(0))
+                .build();
+            (
+                jar.remove(removal),
+                (
+                    StatusCode::OK,
+                    Json(AuthResponse {
+                        ok: true,
+                        message: "Account deleted".into(),
+                        username: None,
+                        user_id: None,
+                        ban: None,
+                    }),
+                ),
+            )
         }
+        Err(e) => (jar, auth_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn export_me(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let (user, token) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return not_logged_in().into_response(),
+    };
 
-        match db.set_skin(user.id, &data, &content_type) {
-            Ok(_) => return (StatusCode::OK, "Skin uploaded").into_response(),
+    let user_id = user.id;
+    match db.run(move |db| db.export_user(user_id, &token)).await {
+        Some(export) => (
+            [(
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}-export.json\"", user.username),
+            )],
+            Json(export),
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, "User not found").into_response(),
+    }
+}
+
+async fn upload_skin(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    if let Some(mut field) = multipart.next_field().await.unwrap_or(None) {
+        // Read in chunks so oversized uploads are cut off early
+        let mut data = Vec::new();
+        loop {
+            match field.chunk().await {
+                Ok(Some(chunk)) => {
+                    if data.len() + chunk.len() > MAX_SKIN_SIZE {
+                        return (StatusCode::BAD_REQUEST, "File too large (max 256KB)")
+                            .into_response();
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(_) => return (StatusCode::BAD_REQUEST, "Failed to read file").into_response(),
+            }
+        }
+
+        // Decoding untrusted images is CPU-bound; run it off the async workers
+        let normalized = tokio::task::spawn_blocking(move || skin_image::normalize_skin(&data))
+            .await
+            .unwrap_or_else(|e| Err(format!("Internal error: {}", e)));
+        let png = match normalized {
+            Ok(png) => png,
+            Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        };
+
+        let user_id = user.id;
+        match db.run(move |db| db.set_skin(user_id, &png, skin_image::SKIN_MIME)).await {
+            Ok(_) => {
+                world
+                    .write()
+                    .await
+                    .set_skin_visibility(user.id, SkinVisibility::OwnerOnly);
+                return (StatusCode::OK, "Skin uploaded, awaiting review").into_response();
+            }
             Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
         }
     }
@@ -178,16 +691,229 @@ async fn upload_skin(
 
 async fn get_skin(
     State(db): State<SharedDb>,
+    jar: CookieJar,
     Path(id): Path<i64>,
 ) -> impl IntoResponse {
-    match db.get_skin(id) {
-        Some(skin) => (
+    let skin = match db.run(move |db| db.get_skin(id)).await {
+        Some(skin) => skin,
+        None => return (StatusCode::NOT_FOUND, "No skin found").into_response(),
+    };
+
+    // Unreviewed skins are only served to their owner and to moderators
+    if skin.status != SkinStatus::Approved {
+        let allowed = session_user(&db, &jar).await
+            .is_some_and(|(user, _)| user.id == id || user.role >= Role::Moderator);
+        if !allowed {
+            return (StatusCode::NOT_FOUND, "No skin found").into_response();
+        }
+        return (
             StatusCode::OK,
             [(header::CONTENT_TYPE, skin.mime),
-             (header::CACHE_CONTROL, "public, max-age=300".into())],
+             (header::CACHE_CONTROL, "private, no-store".into())],
             skin.data,
         )
-            .into_response(),
-        None => (StatusCode::NOT_FOUND, "No skin found").into_response(),
+            .into_response();
     }
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, skin.mime),
+         (header::CACHE_CONTROL, "public, max-age=300".into())],
+        skin.data,
+    )
+        .into_response()
+}
+
+async fn report_skin(
+    State(db): State<SharedDb>,
+    State(world): State<SharedWorld>,
+    jar: CookieJar,
+    Json(req): Json<ReportSkinRequest>,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    let owner_id = world
+        .read()
+        .await
+        .players
+        .get(&req.player_id)
+        .filter(|p| p.skin == SkinVisibility::Public)
+        .and_then(|p| p.user_id);
+    let owner_id = match owner_id {
+        Some(id) if id != user.id => id,
+        Some(_) => return (StatusCode::BAD_REQUEST, "Cannot report your own skin").into_response(),
+        None => return (StatusCode::NOT_FOUND, "No skin found").into_response(),
+    };
+
+    let reason: String = req.reason.trim().chars().take(200).collect();
+    let reporter_id = user.id;
+    match db.run(move |db| db.report_skin(owner_id, reporter_id, &reason)).await {
+        Ok(()) => (StatusCode::OK, "Report received").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+async fn list_friends(
+    State(db): State<SharedDb>,
+    State(presence): State<SharedPresence>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    let friends = match db.run(move |db| db.friends(user.id)).await {
+        Ok(friends) => friends,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    let entries: Vec<FriendEntry> = friends
+        .into_iter()
+        .map(|friend| {
+            let location = (friend.status == FriendStatus::Friends)
+                .then(|| presence.locate(friend.user_id))
+                .flatten();
+            FriendEntry {
+                friend,
+                online: location.is_some(),
+                room: location.map(|l| l.room),
+            }
+        })
+        .collect();
+    Json(entries).into_response()
+}
+
+async fn add_friend(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Json(req): Json<FriendRequest>,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    let username = req.username.trim().to_string();
+    match db.run(move |db| db.request_friend(user.id, &username)).await {
+        Ok(friend) => Json(friend).into_response(),
+        Err(e) if e == "User not found" => (StatusCode::NOT_FOUND, e).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+async fn accept_friend(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Path(from_id): Path<i64>,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    match db.run(move |db| db.accept_friend(user.id, from_id)).await {
+        Ok(()) => (StatusCode::OK, "Friend request accepted").into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e).into_response(),
+    }
+}
+
+async fn remove_friend(
+    State(db): State<SharedDb>,
+    jar: CookieJar,
+    Path(other_id): Path<i64>,
+) -> impl IntoResponse {
+    let (user, _) = match session_user(&db, &jar).await {
+        Some(s) => s,
+        None => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+    };
+
+    match db.run(move |db| db.remove_friend(user.id, other_id)).await {
+        Ok(()) => (StatusCode::OK, "Friend removed").into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e).into_response(),
+    }
+}
+
+/// Whether a scraper may read `/metrics`: from an allowed network, or with
+/// the configured bearer token.
+fn metrics_allowed(addr: SocketAddr, headers: &HeaderMap) -> bool {
+    use sha2::{Digest, Sha256};
+
+    let cfg = settings().metrics.clone();
+    if cfg.allow.iter().any(|net| net.contains(addr.ip())) {
+        return true;
+    }
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    // Compare digests so the check doesn't leak how much of the token matched
+    match (cfg.token, bearer) {
+        (Some(token), Some(given)) => Sha256::digest(token) == Sha256::digest(given),
+        _ => false,
+    }
+}
+
+async fn metrics(
+    State(limiter): State<SharedLimiter>,
+    State(world): State<SharedWorld>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !metrics_allowed(addr, &headers) {
+        return (StatusCode::FORBIDDEN, "Forbidden").into_response();
+    }
+
+    let mut exp = Exposition::default();
+    METRICS.render(&mut exp);
+
+    {
+        let world = world.read().await;
+        let room = [("room", DEFAULT_ROOM)];
+        exp.family("game_players", "gauge", "Players in the world, by room")
+            .sample("game_players", &room, world.players.len() as f64);
+        exp.family("game_entities", "gauge", "Food, viruses, ejected mass, power-ups and mothercells, by room")
+            .sample(
+                "game_entities",
+                &[("room", DEFAULT_ROOM), ("kind", "food")],
+                world.food.len() as f64,
+            )
+            .sample(
+                "game_entities",
+                &[("room", DEFAULT_ROOM), ("kind", "virus")],
+                world.viruses.len() as f64,
+            )
+            .sample(
+                "game_entities",
+                &[("room", DEFAULT_ROOM), ("kind", "ejected")],
+                world.ejected.len() as f64,
+            )
+            .sample(
+                "game_entities",
+                &[("room", DEFAULT_ROOM), ("kind", "power_up")],
+                world.power_ups.len() as f64,
+            )
+            .sample(
+                "game_entities",
+                &[("room", DEFAULT_ROOM), ("kind", "mothercell")],
+                world.mothercells.len() as f64,
+            );
+    }
+
+    let stats = limiter.stats();
+    exp.family(
+        "auth_rate_limit_keys",
+        "gauge",
+        "IPs and usernames currently tracked by the auth rate limiter",
+    )
+    .sample("auth_rate_limit_keys", &[("state", "tracked")], stats.tracked as f64)
+    .sample("auth_rate_limit_keys", &[("state", "locked")], stats.locked as f64);
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        exp.finish(),
+    )
+        .into_response()
 }
@@ -1,2 +1,10 @@
+pub mod admin;
+pub mod connections;
+pub mod console;
+pub mod health;
 pub mod http;
+pub mod presence;
+pub mod ratelimit;
+pub mod shutdown;
+pub mod skin_image;
 pub mod ws;
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+/// Where a logged-in user is currently playing
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    pub room: String,
+    #[serde(skip)]
+    pub player_id: u64,
+}
+
+/// Tracks which accounts have a live player. Fed by the WebSocket handler
+/// on join and leave; a user with several tabs open is online until the
+/// last one closes.
+#[derive(Default)]
+pub struct Presence {
+    online: Mutex<HashMap<i64, Vec<Location>>>,
+}
+
+pub type SharedPresence = Arc<Presence>;
+
+impl Presence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn join(&self, user_id: i64, room: &str, player_id: u64) {
+        self.online
+            .lock()
+            .unwrap()
+            .entry(user_id)
+            .or_default()
+            .push(Location {
+                room: room.to_string(),
+                player_id,
+            });
+    }
+
+    pub fn leave(&self, user_id: i64, player_id: u64) {
+        let mut online = self.online.lock().unwrap();
+        if let Some(locations) = online.get_mut(&user_id) {
+            locations.retain(|l| l.player_id != player_id);
+            if locations.is_empty() {
+                online.remove(&user_id);
+            }
+        }
+    }
+
+    /// The user's most recent live player, if any
+    pub fn locate(&self, user_id: i64) -> Option<Location> {
+        self.online
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .and_then(|locations| locations.last().cloned())
+    }
+}
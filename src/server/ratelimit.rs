@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{settings, RateLimitSettings};
+use crate::metrics::{inc, METRICS};
+
+// Drop idle entries once the table grows past this many keys
+const PRUNE_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(IpAddr),
+    /// Attempts against one username from one IP. Username limits and
+    /// lockouts use this key rather than the bare username so nobody can
+    /// lock a victim out of their own account from elsewhere.
+    UsernameIp(u64, IpAddr),
+}
+
+#[derive(Debug)]
+struct Entry {
+    window_start: Instant,
+    attempts: u32,
+    failures: u32,
+    last_failure: Option<Instant>,
+    locked_until: Option<Instant>,
+}
+
+impl Entry {
+    fn new(now: Instant) -> Self {
+        Entry {
+            window_start: now,
+            attempts: 0,
+            failures: 0,
+            last_failure: None,
+            locked_until: None,
+        }
+    }
+
+    /// True once the attempt window, the last failure and any lockout are
+    /// all more than a window in the past. Stale entries carry no state
+    /// worth keeping: failures are forgotten and the entry can be dropped.
+    fn stale(&self, now: Instant, window: Duration) -> bool {
+        let quiet_since = [Some(self.window_start), self.last_failure, self.locked_until]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or(self.window_start);
+        now >= quiet_since + window
+    }
+}
+
+/// Per-IP and per-username limiter for the auth endpoints. Each IP, and
+/// each username from one IP, gets a fixed number of attempts per window;
+/// consecutive login
+/// failures lock the IP, or the username from that IP, out for an
+/// exponentially growing period. Failures are forgotten a window after the
+/// last failure or lockout.
+#[derive(Default)]
+pub struct RateLimiter {
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+pub struct LimiterStats {
+    pub tracked: usize,
+    pub locked: usize,
+}
+
+fn username_hash(username: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    username.trim().to_lowercase().hash(&mut h);
+    h.finish()
+}
+
+/// Keys that accumulate failures and can be locked out
+fn failure_keys(ip: IpAddr, username: &str) -> [Key; 2] {
+    [Key::Ip(ip), Key::UsernameIp(username_hash(username), ip)]
+}
+
+fn lockout_duration(cfg: &RateLimitSettings, failures: u32) -> Duration {
+    let exp = failures.saturating_sub(cfg.lockout_after).min(20);
+    let secs = cfg.lockout_base_secs.saturating_mul(1 << exp);
+    Duration::from_secs(secs.min(cfg.lockout_max_secs))
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an attempt. Returns how long the caller must wait if the
+    /// attempt is not allowed.
+    pub fn check(&self, ip: IpAddr, username: &str) -> Result<(), Duration> {
+        let cfg = settings().rate_limit.clone();
+        let window = Duration::from_secs(cfg.window_secs);
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() > PRUNE_THRESHOLD {
+            entries.retain(|_, e| !e.stale(now, window));
+        }
+
+        let name = username_hash(username);
+        let keys = [
+            (Key::Ip(ip), cfg.per_ip, &METRICS.rate_limited_ip),
+            (Key::UsernameIp(name, ip), cfg.per_username, &METRICS.rate_limited_username),
+        ];
+
+        // Check every key before counting the attempt against any of them
+        for (key, limit, counter) in &keys {
+            let Some(e) = entries.get(key) else { continue };
+            if e.stale(now, window) {
+                entries.remove(key);
+                continue;
+            }
+            if let Some(until) = e.locked_until.filter(|t| *t > now) {
+                inc(counter);
+                return Err(until - now);
+            }
+            let elapsed = now.duration_since(e.window_start);
+            if elapsed < window && e.attempts >= *limit {
+                inc(counter);
+                return Err(window - elapsed);
+            }
+        }
+
+        for (key, _, _) in &keys {
+            let e = entries.entry(*key).or_insert_with(|| Entry::new(now));
+            if now.duration_since(e.window_start) >= window {
+                e.window_start = now;
+                e.attempts = 0;
+            }
+            e.attempts += 1;
+        }
+        Ok(())
+    }
+
+    /// Counts a failed credential check. Only wrong passwords should be
+    /// reported here, not validation errors such as a taken name.
+    pub fn record_failure(&self, ip: IpAddr, username: &str) {
+        let cfg = settings().rate_limit.clone();
+        let window = Duration::from_secs(cfg.window_secs);
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let labels = [format!("ip {}", ip), format!("username {:?} from {}", username, ip)];
+        for (key, label) in failure_keys(ip, username).into_iter().zip(labels) {
+            let e = entries.entry(key).or_insert_with(|| Entry::new(now));
+            if e.stale(now, window) {
+                *e = Entry::new(now);
+            }
+            e.failures += 1;
+            e.last_failure = Some(now);
+            if e.failures >= cfg.lockout_after {
+                let duration = lockout_duration(&cfg, e.failures);
+                e.locked_until = Some(now + duration);
+                inc(&METRICS.lockouts);
+                tracing::warn!(
+                    "Auth lockout for {} after {} failures ({}s)",
+                    label,
+                    e.failures,
+                    duration.as_secs()
+                );
+            }
+        }
+    }
+
+    /// Clears failures for this username from this IP. The IP's own count
+    /// is left to expire, so logging into a throwaway account does not
+    /// reset a guessing run against other accounts.
+    pub fn record_success(&self, ip: IpAddr, username: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(&Key::UsernameIp(username_hash(username), ip));
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+        LimiterStats {
+            tracked: entries.len(),
+            locked: entries
+                .values()
+                .filter(|e| e.locked_until.is_some_and(|t| t > now))
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn username_quota_is_per_ip() {
+        let limiter = RateLimiter::new();
+        let cfg = settings().rate_limit.clone();
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "192.0.2.2".parse().unwrap();
+
+        for _ in 0..cfg.per_username {
+            assert!(limiter.check(a, "alice").is_ok());
+        }
+        assert!(limiter.check(a, "alice").is_err());
+        assert!(limiter.check(b, "alice").is_ok());
+    }
+
+    #[test]
+    fn failures_lock_out_only_the_failing_ip() {
+        let limiter = RateLimiter::new();
+        let cfg = settings().rate_limit.clone();
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "192.0.2.2".parse().unwrap();
+
+        for _ in 0..cfg.lockout_after {
+            limiter.record_failure(a, "alice");
+        }
+        assert!(limiter.check(a, "alice").is_err());
+        assert!(limiter.check(b, "alice").is_ok());
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+
+use crate::config::settings;
+use crate::db::SharedDb;
+use crate::game::engine::SharedWorld;
+use crate::game::snapshot;
+use crate::protocol::messages::ServerMessage;
+use crate::server::connections::SharedConnections;
+
+/// Resolves on Ctrl-C, or SIGTERM on Unix (what `docker stop` sends).
+async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl-C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Waits for a shutdown signal, then winds the game down: refuses new
+/// joins, warns players with a countdown, saves the world snapshot,
+/// closes their sockets (which saves their stats unless the snapshot
+/// keeps their run going) and saves whatever is left once the drain
+/// timeout runs out. Meant for `axum::serve(..).with_graceful_shutdown`.
+pub async fn on_signal(world: SharedWorld, db: SharedDb, connections: SharedConnections) {
+    signal().await;
+    let cfg = settings().shutdown.clone();
+    println!("🛑 Shutting down in {}s", cfg.countdown_secs);
+
+    connections.start_draining();
+    if cfg.countdown_secs > 0 {
+        connections.broadcast(&ServerMessage::Shutdown {
+            seconds: cfg.countdown_secs,
+        });
+        // A second signal skips the countdown
+        tokio::select! {
+            _ = sleep(Duration::from_secs(cfg.countdown_secs)) => {},
+            _ = signal() => println!("🛑 Skipping countdown"),
+        }
+    }
+
+    // Snapshot before the players leave the world
+    if let Some(path) = &snapshot::configured_path() {
+        snapshot::save_for_restart(&world, path).await;
+    }
+
+    // Each socket's handler records its player's stats as it closes
+    let closing = connections.kick_all("Server is shutting down");
+    let deadline = Instant::now() + Duration::from_secs(cfg.drain_timeout_secs);
+    while !connections.is_empty() && Instant::now() < deadline {
+        sleep(Duration::from_millis(50)).await;
+    }
+    let stuck = connections.len();
+    if stuck > 0 {
+        tracing::warn!("{} of {} connections didn't close in time", stuck, closing);
+    }
+
+    // Save stats for anyone whose handler didn't get to it
+    let leftover: Vec<(i64, u64)> = {
+        let mut world = world.write().await;
+        let ids: Vec<u64> = world.players.keys().copied().collect();
+        let players: Vec<_> = ids.into_iter().filter_map(|id| world.remove_player(id)).collect();
+        if world.saved_for_restart {
+            Vec::new()
+        } else {
+            players
+                .into_iter()
+                .filter_map(|p| p.user_id.map(|uid| (uid, p.score)))
+                .collect()
+        }
+    };
+    if !leftover.is_empty() {
+        let saved = db
+            .run(move |db| {
+                leftover
+                    .into_iter()
+                    .filter(|(uid, score)| db.record_game(*uid, *score).is_ok())
+                    .count()
+            })
+            .await;
+        tracing::info!("Saved stats for {} players at shutdown", saved);
+    }
+    println!("✅ Drained {} connections", closing - stuck);
+}
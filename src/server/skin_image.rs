@@ -0,0 +1,97 @@
+use std::io::Cursor;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, ImageFormat, ImageReader, Limits};
+
+use crate::config::{MAX_SKIN_SIZE, SKIN_MAX_SOURCE_DIMENSION, SKIN_OUTPUT_SIZE};
+
+// Upper bound on decoder allocations; a 4096x4096 RGBA image needs 64MB
+const MAX_DECODE_ALLOC: u64 = 80 * 1024 * 1024;
+
+pub const SKIN_MIME: &str = "image/png";
+
+/// Validates an uploaded skin and re-encodes it as a square PNG of at most
+/// `SKIN_OUTPUT_SIZE` pixels. The format is detected from the file's magic
+/// bytes, never from the client's content type. Re-encoding drops all
+/// metadata and any payload that is not pixel data.
+pub fn normalize_skin(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.is_empty() {
+        return Err("Empty file".into());
+    }
+    if data.len() > MAX_SKIN_SIZE {
+        return Err(format!("File too large (max {}KB)", MAX_SKIN_SIZE / 1024));
+    }
+
+    let format = image::guess_format(data).map_err(|_| "Unsupported image format".to_string())?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+    ) {
+        return Err("Unsupported image format (use PNG, JPEG, GIF or WebP)".into());
+    }
+
+    // Read the header first so oversized images are rejected before decoding
+    let (width, height) = ImageReader::with_format(Cursor::new(data), format)
+        .into_dimensions()
+        .map_err(|_| "Corrupt or unreadable image".to_string())?;
+    if width == 0 || height == 0 {
+        return Err("Image has no pixels".into());
+    }
+    if width > SKIN_MAX_SOURCE_DIMENSION || height > SKIN_MAX_SOURCE_DIMENSION {
+        return Err(format!(
+            "Image too large ({}x{}, max {}x{})",
+            width, height, SKIN_MAX_SOURCE_DIMENSION, SKIN_MAX_SOURCE_DIMENSION
+        ));
+    }
+
+    if is_animated(data, format)? {
+        return Err("Animated images are not allowed".into());
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(SKIN_MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(SKIN_MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+    let img = reader
+        .decode()
+        .map_err(|_| "Corrupt or unreadable image".to_string())?;
+
+    // Center-crop to a square, then downscale
+    let side = width.min(height);
+    let square = img.crop_imm((width - side) / 2, (height - side) / 2, side, side);
+    let out_side = side.min(SKIN_OUTPUT_SIZE);
+    let resized = if out_side < side {
+        square.resize_exact(out_side, out_side, FilterType::Lanczos3)
+    } else {
+        square
+    };
+
+    let mut out = Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgba8(resized.to_rgba8())
+        .write_to(&mut out, ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode skin: {}", e))?;
+    Ok(out.into_inner())
+}
+
+fn is_animated(data: &[u8], format: ImageFormat) -> Result<bool, String> {
+    let corrupt = |_| "Corrupt or unreadable image".to_string();
+    match format {
+        ImageFormat::Png => PngDecoder::new(Cursor::new(data))
+            .and_then(|d| d.is_apng())
+            .map_err(corrupt),
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(data))
+            .map(|d| d.has_animation())
+            .map_err(corrupt),
+        ImageFormat::Gif => {
+            let decoder = GifDecoder::new(Cursor::new(data)).map_err(corrupt)?;
+            Ok(decoder.into_frames().take(2).count() > 1)
+        }
+        _ => Ok(false),
+    }
+}
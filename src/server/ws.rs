@@ -1,34 +1,47 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ConnectInfo, State,
     },
     response::IntoResponse,
 };
 use futures_util::{SinkExt, StreamExt};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::time::{interval, Duration};
 
 use crate::config::*;
-use crate::db::Database;
+use crate::db::bans::Ban;
+use crate::db::skins::SkinStatus;
+use crate::db::tokens::{TokenScope, API_TOKEN_PREFIX};
+use crate::db::SharedDb;
 use crate::game::engine::{build_state_for_player, SharedWorld};
+use crate::game::player::SkinVisibility;
+use crate::names;
+use crate::metrics::{inc, GaugeGuard, METRICS};
+use crate::server::connections::{Outbox, SharedConnections};
+use crate::server::presence::{Location, SharedPresence};
 use crate::protocol::messages::{ClientMessage, ServerMessage};
 
 #[derive(Clone)]
 pub struct WsState {
     pub world: SharedWorld,
-    pub db: Arc<Database>,
+    pub db: SharedDb,
+    pub presence: SharedPresence,
+    pub connections: SharedConnections,
 }
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<WsState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    ws.on_upgrade(move |socket| handle_socket(socket, state, addr))
 }
 
-async fn handle_socket(socket: WebSocket, state: WsState) {
+async fn handle_socket(socket: WebSocket, state: WsState, addr: SocketAddr) {
+    let _socket_gauge = GaugeGuard::new(&METRICS.sockets);
     let (mut sender, mut receiver) = socket.split();
     let player_id = Arc::new(RwLock::new(None::<u64>));
 
@@ -36,60 +49,137 @@ async fn handle_socket(socket: WebSocket, state: WsState) {
     let join_msg = match receiver.next().await {
         Some(Ok(Message::Text(text))) => {
             match serde_json::from_str::<ClientMessage>(&text) {
-                Ok(ClientMessage::Join { name, token }) => Some((name, token)),
+                Ok(ClientMessage::Join {
+                    name,
+                    token,
+                    friend,
+                    resume,
+                }) => Some((name, token, friend, resume)),
                 _ => None,
             }
         }
         _ => None,
     };
 
-    let (name, token) = match join_msg {
+    let (name, token, friend, resume) = match join_msg {
         Some(j) => j,
         None => {
             let _ = sender
                 .send(Message::Text(
-                    serde_json::to_string(&ServerMessage::Error {
+                    ServerMessage::Error {
                         message: "Expected join message".into(),
-                    })
-                    .unwrap()
-                    .into(),
+                        ban: None,
+                    }
+                    .encode(),
                 ))
                 .await;
             return;
         }
     };
 
-    // Resolve user_id from token
-    let user_id = token
-        .as_deref()
-        .and_then(|t| state.db.validate_session(t))
-        .map(|u| u.id);
+    if state.connections.is_draining() {
+        let _ = sender
+            .send(Message::Text(
+                ServerMessage::Error {
+                    message: "Server is shutting down".into(),
+                    ban: None,
+                }
+                .encode(),
+            ))
+            .await;
+        return;
+    }
 
-    let display_name = if name.trim().is_empty() {
-        "Unnamed".to_string()
-    } else {
-        name.chars().take(20).collect()
+    let join = match resolve_join(&state, &name, token, friend).await {
+        Ok(join) => join,
+        Err(message) => {
+            let _ = sender
+                .send(Message::Text(
+                    ServerMessage::Error { message, ban: None }.encode(),
+                ))
+                .await;
+            return;
+        }
+    };
+    let user_id = join.user_id;
+
+    // Bans are checked before the player exists
+    let ip = addr.ip();
+    let refusal = match state.db.run(move |db| db.find_ban(user_id, Some(ip))).await {
+        Ok(None) => None,
+        Ok(Some(ban)) => {
+            tracing::info!("Refused banned client {} (ban {})", ip, ban.id);
+            Some(ban_error(&ban))
+        }
+        Err(e) => {
+            tracing::error!("Ban lookup for {} failed: {}", ip, e);
+            Some(ban_check_failed())
+        }
     };
+    if let Some(refusal) = refusal {
+        let _ = sender.send(Message::Text(refusal.encode())).await;
+        return;
+    }
+
+    // Battle royale rounds can't be joined once they've started
+    let open = state.world.read().await.can_join();
+    if let Err(message) = open {
+        let _ = sender
+            .send(Message::Text(
+                ServerMessage::Error { message, ban: None }.encode(),
+            ))
+            .await;
+        return;
+    }
 
-    // Add player to world
-    let id = {
+    // Take back a player restored after a restart, or add a new one
+    let (id, resume_token, resumed) = {
         let mut world = state.world.write().await;
-        world.add_player(display_name, user_id)
+        let reclaimed = resume.and_then(|token| world.reclaim(&token, user_id));
+        let id = match reclaimed {
+            Some(id) => id,
+            None => {
+                let id = world.add_player(join.name.clone(), user_id);
+                if let Some(location) = &join.friend_location {
+                    world.spawn_near(id, location.player_id);
+                }
+                id
+            }
+        };
+        let player = world.players.get_mut(&id).expect("player was just added");
+        // A reclaimed player takes the name it rejoined with, which is the
+        // name `verified` was worked out for
+        player.name = join.name;
+        player.skin = join.skin;
+        player.verified = join.verified;
+        player.bot = join.bot;
+        (id, player.resume_token.clone(), reclaimed.is_some())
     };
+    if resumed {
+        tracing::info!("Player {} reclaimed after restart", id);
+    }
     *player_id.write().await = Some(id);
+    if let Some(uid) = user_id {
+        state.presence.join(uid, &join.room, id);
+    }
 
     // Send joined confirmation
-    let joined_msg = serde_json::to_string(&ServerMessage::Joined {
+    let room = join.room.clone();
+    let joined_msg = ServerMessage::Joined {
         id,
         world_size: WORLD_SIZE,
-    })
-    .unwrap();
-    if sender.send(Message::Text(joined_msg.into())).await.is_err() {
-        cleanup(&state.world, id).await;
+        room: join.room,
+        resume_token,
+        resumed,
+    }
+    .encode();
+    if sender.send(Message::Text(joined_msg)).await.is_err() {
+        cleanup(&state, id).await;
         return;
     }
 
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let (tx, mut rx) = Outbox::channel();
+    state.connections.register(id, addr.ip(), tx.clone());
 
     // Task: send game state to client at tick rate
     let world_clone = state.world.clone();
@@ -103,12 +193,10 @@ async fn handle_socket(socket: WebSocket, state: WsState) {
             // Check if player is dead
             if let Some(player) = world.players.get(&id) {
                 if !player.alive {
-                    let dead_msg = serde_json::to_string(&ServerMessage::Dead {
+                    tx_clone.send(&ServerMessage::Dead {
                         killer: world.get_killer_name(id),
                         score: player.score,
-                    })
-                    .unwrap();
-                    let _ = tx_clone.send(dead_msg);
+                    });
                     break;
                 }
             } else {
@@ -116,18 +204,19 @@ async fn handle_socket(socket: WebSocket, state: WsState) {
             }
 
             if let Some(state_msg) = build_state_for_player(&world, id) {
-                let json = serde_json::to_string(&state_msg).unwrap();
-                if tx_clone.send(json).is_err() {
+                if !tx_clone.send_droppable(&state_msg) {
                     break;
                 }
             }
         }
     });
 
-    // Task: forward messages from channel to websocket
+    // Task: forward messages from channel to websocket. A queued Close
+    // (e.g. from a kick) ends the connection.
     let forward_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            if sender.send(Message::Text(msg.into())).await.is_err() {
+            let closing = matches!(msg, Message::Close(_));
+            if sender.send(msg).await.is_err() || closing {
                 break;
             }
         }
@@ -135,6 +224,8 @@ async fn handle_socket(socket: WebSocket, state: WsState) {
 
     // Main loop: receive input from client
     let world_clone = state.world.clone();
+    let db = state.db.clone();
+    let presence = state.presence.clone();
     let recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             match msg {
@@ -148,13 +239,31 @@ async fn handle_socket(socket: WebSocket, state: WsState) {
                                     player.target_y = y;
                                 }
                             }
-                            ClientMessage::Split => {
+                            ClientMessage::Split { times } => {
                                 let mut world = world_clone.write().await;
-                                world.split_player(id);
+                                if !world.request_split(id, times) {
+                                    inc(&METRICS.splits_limited);
+                                }
                             }
                             ClientMessage::Eject => {
                                 let mut world = world_clone.write().await;
-                                world.eject_mass(id);
+                                if !world.request_eject(id) {
+                                    inc(&METRICS.ejects_limited);
+                                }
+                            }
+                            ClientMessage::EjectStart | ClientMessage::EjectStop => {
+                                let held = matches!(client_msg, ClientMessage::EjectStart);
+                                let mut world = world_clone.write().await;
+                                if let Some(player) = world.players.get_mut(&id) {
+                                    player.ejecting = held;
+                                }
+                            }
+                            ClientMessage::Attach { token } => {
+                                let reply = attach_account(&world_clone, &db, id, addr.ip(), token).await;
+                                if let ServerMessage::Attached { user_id, .. } = &reply {
+                                    presence.join(*user_id, &room, id);
+                                }
+                                tx.send(&reply);
                             }
                             _ => {}
                         }
@@ -173,10 +282,182 @@ async fn handle_socket(socket: WebSocket, state: WsState) {
         _ = recv_task => {},
     }
 
-    cleanup(&state.world, id).await;
+    cleanup(&state, id).await;
+}
+
+/// Everything needed to spawn a player, checked before it enters the world
+struct JoinInfo {
+    name: String,
+    user_id: Option<i64>,
+    skin: SkinVisibility,
+    /// Playing under their own registered username
+    verified: bool,
+    /// Joined with an API token
+    bot: bool,
+    friend_location: Option<Location>,
+    room: String,
+}
+
+async fn resolve_join(
+    state: &WsState,
+    name: &str,
+    token: Option<String>,
+    friend: Option<i64>,
+) -> Result<JoinInfo, String> {
+    let name = names::display_name(name)?;
+
+    // Resolve the account, and whoever owns the requested name
+    let key = names::name_key(&name);
+    let (user_id, bot, skin_status, is_friend, name_owner) = state
+        .db
+        .run(move |db| {
+            // API tokens join as bots and need the play scope; a stale
+            // session token just falls back to playing as a guest
+            let (user_id, bot) = match token {
+                Some(t) if t.starts_with(API_TOKEN_PREFIX) => match db.validate_token(&t) {
+                    Some((user, scopes)) if scopes.contains(&TokenScope::Play) => {
+                        (Some(user.id), true)
+                    }
+                    Some(_) => return Err("This token doesn't have the play scope"),
+                    None => return Err("Invalid API token"),
+                },
+                Some(t) => (db.validate_session(&t).map(|u| u.id), false),
+                None => (None, false),
+            };
+            let is_friend = match (user_id, friend) {
+                (Some(uid), Some(fid)) => db.are_friends(uid, fid),
+                _ => false,
+            };
+            let name_owner = db.find_user_by_name_key(&key).map(|u| u.id);
+            let skin_status = user_id.and_then(|uid| db.skin_status(uid));
+            Ok((user_id, bot, skin_status, is_friend, name_owner))
+        })
+        .await?;
+
+    // Registered names are reserved for their owners
+    let verified = match name_owner {
+        Some(owner) if Some(owner) == user_id => true,
+        Some(_) => return Err("That name belongs to a registered player".into()),
+        None => false,
+    };
+
+    // Party join: follow a friend into their room
+    let friend_location = match friend {
+        Some(_) if user_id.is_none() => return Err("Log in to join friends".into()),
+        Some(_) if !is_friend => return Err("You can only join your friends".into()),
+        Some(friend_id) => Some(
+            state
+                .presence
+                .locate(friend_id)
+                .ok_or("Friend is not online")?,
+        ),
+        None => None,
+    };
+    let room = friend_location
+        .as_ref()
+        .map_or(DEFAULT_ROOM.to_string(), |l| l.room.clone());
+
+    Ok(JoinInfo {
+        name,
+        user_id,
+        skin: skin_visibility(skin_status),
+        verified,
+        bot,
+        friend_location,
+        room,
+    })
+}
+
+/// The error sent to a banned client
+fn ban_error(ban: &Ban) -> ServerMessage {
+    let notice = ban.notice();
+    ServerMessage::Error {
+        message: notice.message.clone(),
+        ban: Some(notice),
+    }
+}
+
+/// Sent instead of letting a client in when its ban lookup failed
+fn ban_check_failed() -> ServerMessage {
+    ServerMessage::Error {
+        message: "Server error, please try again later".into(),
+        ban: None,
+    }
+}
+
+fn skin_visibility(status: Option<SkinStatus>) -> SkinVisibility {
+    match status {
+        Some(SkinStatus::Approved) => SkinVisibility::Public,
+        Some(SkinStatus::Pending) => SkinVisibility::OwnerOnly,
+        Some(SkinStatus::Rejected) | None => SkinVisibility::Hidden,
+    }
 }
 
-async fn cleanup(world: &SharedWorld, player_id: u64) {
-    let mut w = world.write().await;
-    w.remove_player(player_id);
+/// Upgrades a guest to the account behind `token`, keeping its cells.
+/// Stats for the run are recorded against the account when it ends.
+async fn attach_account(
+    world: &SharedWorld,
+    db: &SharedDb,
+    player_id: u64,
+    ip: IpAddr,
+    token: String,
+) -> ServerMessage {
+    let resolved = db
+        .run(move |db| {
+            db.validate_session(&token).map(|user| {
+                let ban = db.find_ban(Some(user.id), Some(ip));
+                (user.id, user.username, db.skin_status(user.id), ban)
+            })
+        })
+        .await;
+    let Some((user_id, username, skin_status, ban)) = resolved else {
+        return ServerMessage::Error {
+            message: "Invalid session".into(),
+            ban: None,
+        };
+    };
+    match ban {
+        Ok(None) => {}
+        Ok(Some(ban)) => return ban_error(&ban),
+        Err(e) => {
+            tracing::error!("Ban lookup for account {} failed: {}", user_id, e);
+            return ban_check_failed();
+        }
+    }
+
+    let mut world = world.write().await;
+    let verified = world
+        .players
+        .get(&player_id)
+        .is_some_and(|p| names::name_key(&p.name) == names::name_key(&username));
+    match world.attach_account(player_id, user_id, skin_visibility(skin_status), verified) {
+        Ok(()) => {
+            tracing::info!("Player {} attached to account {}", player_id, username);
+            ServerMessage::Attached { user_id, username }
+        }
+        Err(message) => ServerMessage::Error { message, ban: None },
+    }
+}
+
+async fn cleanup(state: &WsState, player_id: u64) {
+    let (player, saved_for_restart) = {
+        let mut w = state.world.write().await;
+        (w.remove_player(player_id), w.saved_for_restart)
+    };
+
+    // Record the finished game for logged-in players. A run saved for the
+    // restart isn't finished; it's recorded when it ends after that.
+    if let Some(player) = player {
+        if let Some(user_id) = player.user_id {
+            state.presence.leave(user_id, player_id);
+            let score = player.score;
+            if !saved_for_restart {
+                if let Err(e) = state.db.run(move |db| db.record_game(user_id, score)).await {
+                    tracing::warn!("Failed to record stats for user {}: {}", user_id, e);
+                }
+            }
+        }
+    }
+    // Last, so shutdown only sees the connection gone once stats are saved
+    state.connections.unregister(player_id);
 }
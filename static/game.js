@@ -11,7 +11,10 @@ const Game = {
     players: [],
     food: [],
     viruses: [],
+    powerUps: [],
+    mothercells: [],
     leaderboard: [],
+    round: null,
 
     // Previous state for interpolation
     prevPlayers: [],
@@ -55,15 +58,31 @@ const Game = {
             this.sendMove();
         }, { passive: false });
 
+        // Right-click a skinned cell to report its skin
+        this.canvas.addEventListener('contextmenu', (e) => {
+            e.preventDefault();
+            this.reportSkinAt(this.mouse.worldX, this.mouse.worldY);
+        });
+
         // Keyboard
         window.addEventListener('keydown', (e) => {
             if (e.code === 'Space') {
                 e.preventDefault();
-                this.sendSplit();
-            } else if (e.code === 'KeyW') {
-                this.sendEject();
+                this.sendSplit(1);
+            } else if (e.code === 'KeyD') {
+                this.sendSplit(2);
+            } else if (e.code === 'KeyF') {
+                this.sendSplit(4);
+            } else if (e.code === 'KeyW' && !e.repeat) {
+                this.sendEject(true);
+            }
+        });
+        window.addEventListener('keyup', (e) => {
+            if (e.code === 'KeyW') {
+                this.sendEject(false);
             }
         });
+        window.addEventListener('blur', () => this.sendEject(false));
     },
 
     resize() {
@@ -71,7 +90,7 @@ const Game = {
         this.canvas.height = window.innerHeight;
     },
 
-    connect(name, token) {
+    connect(name, token, friendId) {
         if (this.ws) {
             this.ws.close();
         }
@@ -84,6 +103,9 @@ const Game = {
                 type: 'join',
                 name: name,
                 token: token || null,
+                friend: friendId || null,
+                // Lets this tab take its player back after a server restart
+                resume: sessionStorage.getItem('resumeToken'),
             }));
         };
 
@@ -107,6 +129,7 @@ const Game = {
     handleMessage(msg) {
         switch (msg.type) {
             case 'joined':
+                sessionStorage.setItem('resumeToken', msg.resume_token);
                 this.playerId = msg.id;
                 this.worldSize = msg.world_size;
                 this.running = true;
@@ -119,7 +142,10 @@ const Game = {
                 this.players = msg.players;
                 this.food = msg.food;
                 this.viruses = msg.viruses;
+                this.powerUps = msg.power_ups || [];
+                this.mothercells = msg.mothercells || [];
                 this.leaderboard = msg.leaderboard;
+                this.round = msg.round || null;
                 this.lastStateTime = performance.now();
                 this.interpFactor = 0;
 
@@ -141,9 +167,11 @@ const Game = {
 
                     UI.updateScore(Math.floor(totalMass));
                 }
+                UI.updateEffects(me ? me.effects || [] : []);
 
                 // Update leaderboard
                 UI.updateLeaderboard(this.leaderboard, this.playerId);
+                UI.updateRound(this.round);
 
                 // Preload skins
                 for (const p of this.players) {
@@ -154,6 +182,7 @@ const Game = {
                 break;
 
             case 'dead':
+                sessionStorage.removeItem('resumeToken');
                 this.running = false;
                 if (this.animFrame) {
                     cancelAnimationFrame(this.animFrame);
@@ -161,8 +190,31 @@ const Game = {
                 UI.showDeath(msg.killer, msg.score);
                 break;
 
+            case 'attached':
+                UI.onAttached(msg.username);
+                break;
+
+            case 'announcement':
+                UI.showAnnouncement(msg.message);
+                break;
+
+            case 'shutdown':
+                UI.showShutdown(msg.seconds);
+                break;
+
+            case 'kicked':
+                this.running = false;
+                if (this.animFrame) {
+                    cancelAnimationFrame(this.animFrame);
+                }
+                UI.showJoinError(`Kicked: ${msg.reason}`);
+                break;
+
             case 'error':
                 console.error('Server error:', msg.message);
+                if (!this.running) {
+                    UI.showJoinError(msg.message);
+                }
                 break;
         }
     },
@@ -202,15 +254,41 @@ const Game = {
         }
     },
 
-    sendSplit() {
+    attach(token) {
         if (this.ws && this.ws.readyState === WebSocket.OPEN) {
-            this.ws.send(JSON.stringify({ type: 'split' }));
+            this.ws.send(JSON.stringify({ type: 'attach', token }));
         }
     },
 
-    sendEject() {
+    sendSplit(times) {
         if (this.ws && this.ws.readyState === WebSocket.OPEN) {
-            this.ws.send(JSON.stringify({ type: 'eject' }));
+            this.ws.send(JSON.stringify({ type: 'split', times }));
+        }
+    },
+
+    // Holding W keeps ejecting at the server's rate
+    sendEject(held) {
+        if (this.ws && this.ws.readyState === WebSocket.OPEN) {
+            this.ws.send(JSON.stringify({ type: held ? 'eject_start' : 'eject_stop' }));
+        }
+    },
+
+    async reportSkinAt(x, y) {
+        const target = this.players.find(p =>
+            p.id !== this.playerId && p.skin &&
+            p.cells.some(c => Math.hypot(c.x - x, c.y - y) < c.radius));
+        if (!target) return;
+        const reason = prompt(`Report the skin of "${target.name}"? Reason:`);
+        if (!reason) return;
+        try {
+            const res = await fetch('/api/skin/report', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ player_id: target.id, reason }),
+            });
+            alert(await res.text());
+        } catch (e) {
+            alert('Report failed');
         }
     },
 
@@ -257,12 +335,21 @@ const Game = {
         // Draw world border
         this.drawBorder(ctx);
 
+        // Draw battle royale zone
+        this.drawZone(ctx);
+
         // Draw food
         this.drawFood(ctx);
 
+        // Draw mothercells
+        this.drawMothercells(ctx);
+
         // Draw viruses
         this.drawViruses(ctx);
 
+        // Draw power-ups
+        this.drawPowerUps(ctx);
+
         // Draw players (sorted by mass, smallest first)
         const sortedPlayers = [...this.players].sort((a, b) => {
             const massA = a.cells.reduce((s, c) => s + c.radius * c.radius, 0);
@@ -312,6 +399,37 @@ const Game = {
         ctx.strokeRect(0, 0, this.worldSize, this.worldSize);
     },
 
+    drawZone(ctx) {
+        if (!this.round) return;
+        const zone = this.round.zone;
+
+        // Darken everything outside the safe circle
+        ctx.save();
+        ctx.beginPath();
+        ctx.rect(0, 0, this.worldSize, this.worldSize);
+        ctx.arc(zone.x, zone.y, Math.max(zone.radius, 0), 0, Math.PI * 2, true);
+        ctx.fillStyle = 'rgba(120, 0, 40, 0.25)';
+        ctx.fill();
+        ctx.restore();
+
+        ctx.strokeStyle = 'rgba(80, 200, 255, 0.8)';
+        ctx.lineWidth = 6;
+        ctx.beginPath();
+        ctx.arc(zone.x, zone.y, Math.max(zone.radius, 0), 0, Math.PI * 2);
+        ctx.stroke();
+
+        const next = this.round.next_zone;
+        if (next) {
+            ctx.strokeStyle = 'rgba(255, 255, 255, 0.5)';
+            ctx.lineWidth = 3;
+            ctx.setLineDash([20, 15]);
+            ctx.beginPath();
+            ctx.arc(next.x, next.y, Math.max(next.radius, 0), 0, Math.PI * 2);
+            ctx.stroke();
+            ctx.setLineDash([]);
+        }
+    },
+
     drawFood(ctx) {
         for (const f of this.food) {
             ctx.fillStyle = f.color;
@@ -347,7 +465,63 @@ const Game = {
         }
     },
 
+    drawMothercells(ctx) {
+        for (const m of this.mothercells) {
+            // Wobbly pink cell with a darker nucleus
+            const bumps = 24;
+            const t = performance.now() / 400;
+            ctx.fillStyle = 'rgba(230, 80, 140, 0.55)';
+            ctx.strokeStyle = '#e6508c';
+            ctx.lineWidth = 3;
+            ctx.beginPath();
+            for (let i = 0; i <= bumps; i++) {
+                const angle = (i / bumps) * Math.PI * 2;
+                const r = m.radius * (1 + Math.sin(angle * 6 + t) * 0.03);
+                const px = m.x + Math.cos(angle) * r;
+                const py = m.y + Math.sin(angle) * r;
+                if (i === 0) ctx.moveTo(px, py);
+                else ctx.lineTo(px, py);
+            }
+            ctx.closePath();
+            ctx.fill();
+            ctx.stroke();
+
+            ctx.fillStyle = 'rgba(150, 30, 90, 0.6)';
+            ctx.beginPath();
+            ctx.arc(m.x, m.y, m.radius * 0.35, 0, Math.PI * 2);
+            ctx.fill();
+        }
+    },
+
+    drawPowerUps(ctx) {
+        const styles = {
+            speed: { color: '#ffd633', icon: '⚡' },
+            shield: { color: '#66ccff', icon: '🛡' },
+            magnet: { color: '#ff6666', icon: '🧲' },
+            merge: { color: '#b388ff', icon: '⊕' },
+        };
+        const pulse = 1 + Math.sin(performance.now() / 200) * 0.08;
+        for (const p of this.powerUps) {
+            const style = styles[p.kind] || { color: '#ffffff', icon: '?' };
+            const r = 18 * pulse;
+            ctx.fillStyle = 'rgba(0, 0, 0, 0.4)';
+            ctx.strokeStyle = style.color;
+            ctx.lineWidth = 3;
+            ctx.beginPath();
+            ctx.arc(p.x, p.y, r, 0, Math.PI * 2);
+            ctx.fill();
+            ctx.stroke();
+
+            ctx.fillStyle = style.color;
+            ctx.font = '18px sans-serif';
+            ctx.textAlign = 'center';
+            ctx.textBaseline = 'middle';
+            ctx.fillText(style.icon, p.x, p.y + 1);
+        }
+    },
+
     drawPlayer(ctx, player) {
+        const shielded = (player.effects || []).some(e => e.kind === 'shield');
         const isMe = player.id === this.playerId;
         const skinImg = player.skin ? this.skinImages[player.skin] : null;
 
@@ -398,6 +572,15 @@ const Game = {
             if (isMe) {
                 ctx.restore();
             }
+
+            // Shield bubble
+            if (shielded) {
+                ctx.strokeStyle = 'rgba(102, 204, 255, 0.7)';
+                ctx.lineWidth = Math.max(3, r * 0.05);
+                ctx.beginPath();
+                ctx.arc(x, y, r + 6, 0, Math.PI * 2);
+                ctx.stroke();
+            }
         }
 
         // Draw name (on the largest cell)
@@ -409,13 +592,17 @@ const Game = {
             ctx.textAlign = 'center';
             ctx.textBaseline = 'middle';
 
+            // Registered players playing under their own username, and bots
+            let label = player.verified ? `✔ ${player.name}` : player.name;
+            if (player.bot) label = `🤖 ${label}`;
+
             // Text shadow
             ctx.fillStyle = 'rgba(0,0,0,0.5)';
-            ctx.fillText(player.name, largest.x + 2, largest.y + 2);
+            ctx.fillText(label, largest.x + 2, largest.y + 2);
 
             // Text
             ctx.fillStyle = '#fff';
-            ctx.fillText(player.name, largest.x, largest.y);
+            ctx.fillText(label, largest.x, largest.y);
 
             // Mass text (smaller, below name)
             if (isMe && largest.radius > 30) {
@@ -8,6 +8,7 @@ const UI = {
     authMessage: null,
     loggedInUser: null,
     sessionToken: null,
+    playingAsGuest: false,
 
     init() {
         this.menuOverlay = document.getElementById('menuOverlay');
@@ -19,6 +20,7 @@ const UI = {
         // Play button
         document.getElementById('playBtn').addEventListener('click', () => this.play());
         document.getElementById('respawnBtn').addEventListener('click', () => this.play());
+        document.getElementById('saveRunBtn').addEventListener('click', () => this.showLoginInGame());
 
         // Enter key to play
         this.playerNameInput.addEventListener('keydown', (e) => {
@@ -30,6 +32,9 @@ const UI = {
         document.getElementById('registerBtn').addEventListener('click', () => this.register());
         document.getElementById('logoutBtn').addEventListener('click', () => this.logout());
 
+        // Friends
+        document.getElementById('addFriendBtn').addEventListener('click', () => this.addFriend());
+
         // Skin upload
         document.getElementById('skinUpload').addEventListener('change', (e) => this.uploadSkin(e));
 
@@ -58,6 +63,7 @@ const UI = {
         document.getElementById('loggedInName').textContent = username;
         document.getElementById('authStatus').textContent = `Logged in as ${username}`;
         this.playerNameInput.value = username;
+        this.loadFriends();
 
         // Show skin preview
         const preview = document.getElementById('skinPreview');
@@ -80,6 +86,130 @@ const UI = {
         document.getElementById('loggedInInfo').style.display = 'none';
         document.getElementById('authStatus').textContent = '';
         document.getElementById('skinPreview').style.display = 'none';
+        document.getElementById('friendsList').innerHTML = '';
+    },
+
+    async loadFriends() {
+        if (!this.loggedInUser) return;
+        try {
+            const res = await fetch('/api/friends');
+            if (!res.ok) return;
+            this.renderFriends(await res.json());
+        } catch (e) {
+            console.log('Failed to load friends');
+        }
+    },
+
+    renderFriends(friends) {
+        const list = document.getElementById('friendsList');
+        list.innerHTML = '';
+        friends.forEach(friend => {
+            const li = document.createElement('li');
+            const name = document.createElement('span');
+            name.className = 'friend-name';
+            if (friend.status === 'friends') {
+                name.textContent = `${friend.online ? '🟢' : '⚪'} ${friend.username}`;
+                name.classList.add(friend.online ? 'friend-online' : 'friend-offline');
+            } else {
+                name.textContent = `${friend.username} (${friend.status === 'incoming' ? 'wants to be friends' : 'pending'})`;
+                name.classList.add('friend-pending');
+            }
+            li.appendChild(name);
+
+            if (friend.status === 'incoming') {
+                li.appendChild(this.friendButton('Accept', () => this.friendAction('POST', `/api/friends/${friend.user_id}/accept`)));
+            }
+            if (friend.status === 'friends' && friend.online) {
+                li.appendChild(this.friendButton('Join', () => this.joinFriend(friend.user_id)));
+            }
+            const removeLabel = friend.status === 'friends' ? 'Remove' : (friend.status === 'incoming' ? 'Decline' : 'Cancel');
+            li.appendChild(this.friendButton(removeLabel, () => this.friendAction('DELETE', `/api/friends/${friend.user_id}`)));
+            list.appendChild(li);
+        });
+    },
+
+    friendButton(label, onClick) {
+        const btn = document.createElement('button');
+        btn.className = 'btn btn-auth';
+        btn.textContent = label;
+        btn.addEventListener('click', onClick);
+        return btn;
+    },
+
+    async addFriend() {
+        const input = document.getElementById('friendName');
+        const username = input.value.trim();
+        if (!username) return;
+        try {
+            const res = await fetch('/api/friends', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ username }),
+            });
+            if (res.ok) {
+                const friend = await res.json();
+                input.value = '';
+                this.showFriendsMsg(friend.status === 'friends' ? `You and ${friend.username} are now friends` : 'Friend request sent', true);
+                this.loadFriends();
+            } else {
+                this.showFriendsMsg(await res.text(), false);
+            }
+        } catch (e) {
+            this.showFriendsMsg('Connection error', false);
+        }
+    },
+
+    async friendAction(method, url) {
+        try {
+            const res = await fetch(url, { method });
+            this.showFriendsMsg(await res.text(), res.ok);
+            this.loadFriends();
+        } catch (e) {
+            this.showFriendsMsg('Connection error', false);
+        }
+    },
+
+    joinFriend(friendId) {
+        this.play(friendId);
+    },
+
+    showFriendsMsg(msg, success) {
+        const el = document.getElementById('friendsMessage');
+        el.textContent = msg;
+        el.className = 'auth-message ' + (success ? 'success' : 'error');
+    },
+
+    // Server-wide message from an admin
+    showAnnouncement(message) {
+        const el = document.getElementById('announcement');
+        el.textContent = `📢 ${message}`;
+        el.style.display = 'block';
+        clearTimeout(this.announcementTimer);
+        this.announcementTimer = setTimeout(() => {
+            el.style.display = 'none';
+        }, 8000);
+    },
+
+    // Server is going down; count down in the announcement banner
+    showShutdown(seconds) {
+        const end = Date.now() + seconds * 1000;
+        clearInterval(this.shutdownTimer);
+        const update = () => {
+            const left = Math.max(0, Math.ceil((end - Date.now()) / 1000));
+            this.showAnnouncement(`Server restarting in ${left}s`);
+            if (left === 0) {
+                clearInterval(this.shutdownTimer);
+            }
+        };
+        update();
+        this.shutdownTimer = setInterval(update, 1000);
+    },
+
+    // The server refused to let us in (e.g. friend went offline)
+    showJoinError(message) {
+        this.showMenu();
+        this.showFriendsMsg(message, false);
+        this.showAuthMsg(message, false);
     },
 
     async login() {
@@ -99,6 +229,10 @@ const UI = {
             if (data.ok) {
                 this.showAuthMsg('Logged in!', true);
                 this.setLoggedIn(data.username, data.user_id);
+                // Upgrade the running guest instead of starting over
+                if (this.playingAsGuest && Game.running) {
+                    Game.attach(this.sessionToken);
+                }
             } else {
                 this.showAuthMsg(data.message, false);
             }
@@ -153,6 +287,7 @@ const UI = {
                 body: formData,
             });
             if (res.ok) {
+                this.showAuthMsg('Skin uploaded! Others will see it once approved.', true);
                 // Refresh preview
                 if (this.loggedInUser) {
                     const preview = document.getElementById('skinPreview');
@@ -165,21 +300,40 @@ const UI = {
                     };
                 }
             } else {
-                alert('Failed to upload skin');
+                alert(`Failed to upload skin: ${await res.text()}`);
             }
         } catch (e) {
             alert('Upload error');
         }
     },
 
-    play() {
-        const name = this.playerNameInput.value.trim() || 'Unnamed';
+    play(friendId) {
         this.menuOverlay.style.display = 'none';
         this.deathOverlay.style.display = 'none';
         this.hud.style.display = 'block';
 
+        // Menu was opened from the HUD: go back to the current run
+        if (Game.running && !friendId) return;
+
+        const name = this.playerNameInput.value.trim() || 'Unnamed';
+        this.playingAsGuest = !this.sessionToken;
+        document.getElementById('saveRunBtn').style.display = this.playingAsGuest ? 'block' : 'none';
+
         // Start game
-        Game.connect(name, this.sessionToken);
+        Game.connect(name, this.sessionToken, friendId);
+    },
+
+    showLoginInGame() {
+        this.menuOverlay.style.display = 'flex';
+        this.hud.style.display = 'none';
+    },
+
+    onAttached(username) {
+        this.playingAsGuest = false;
+        document.getElementById('saveRunBtn').style.display = 'none';
+        document.getElementById('authStatus').textContent = `Logged in as ${username} — this run counts`;
+        this.menuOverlay.style.display = 'none';
+        this.hud.style.display = 'block';
     },
 
     showDeath(killer, score) {
@@ -192,6 +346,7 @@ const UI = {
     },
 
     showMenu() {
+        this.loadFriends();
         this.menuOverlay.style.display = 'flex';
         this.deathOverlay.style.display = 'none';
         this.hud.style.display = 'none';
@@ -207,6 +362,40 @@ const UI = {
         });
     },
 
+    updateRound(round) {
+        const el = document.getElementById('roundStatus');
+        if (!round) {
+            el.style.display = 'none';
+            return;
+        }
+        const secs = round.seconds !== undefined ? ` ${round.seconds}s` : '';
+        let text;
+        switch (round.phase) {
+            case 'lobby':
+                text = `Waiting for players (${round.alive}/${round.min_players})`;
+                break;
+            case 'starting':
+                text = `Round starts in${secs}`;
+                break;
+            case 'running':
+                text = `${round.alive} alive` + (secs ? ` — zone moves in${secs}` : '');
+                break;
+            case 'finished':
+                text = `${round.winner ? round.winner + ' wins!' : 'No winner'} Next round in${secs}`;
+                break;
+        }
+        el.textContent = text;
+        el.style.display = 'block';
+    },
+
+    updateEffects(effects) {
+        const names = { speed: '⚡ Speed', shield: '🛡 Shield', magnet: '🧲 Magnet', merge: '⊕ Fast merge' };
+        const el = document.getElementById('effectsDisplay');
+        el.textContent = effects
+            .map(e => `${names[e.kind] || e.kind} ${Math.ceil(e.seconds)}s`)
+            .join('  ');
+    },
+
     updateScore(score) {
         document.getElementById('scoreDisplay').textContent = `Score: ${score}`;
     },